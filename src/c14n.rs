@@ -0,0 +1,148 @@
+use crate::document::{Document, Node};
+use crate::element::{check_node_text, Element};
+use crate::error::{Error, Result};
+use quick_xml::escape::unescape;
+use std::collections::HashMap;
+
+/// Write `nodes` (the root nodes of `doc`) in canonical form, see [`Document::write_c14n`].
+pub(crate) fn write_root_nodes(doc: &Document, nodes: &[Node], out: &mut String) -> Result<()> {
+    let mut after_root = false;
+    for node in nodes {
+        match node {
+            Node::Element(elem) => {
+                write_element(doc, *elem, &HashMap::new(), out)?;
+                after_root = true;
+            }
+            Node::PI(text) => {
+                check_node_text(node)?;
+                if after_root {
+                    out.push('\n');
+                }
+                write_pi(text, out);
+                if !after_root {
+                    out.push('\n');
+                }
+            }
+            // Text outside of the root element, comments and the doctype are not canonical.
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Write `elem` and its sub-tree. `rendered` contains the namespace declarations
+/// which are already in scope in the output.
+fn write_element(
+    doc: &Document,
+    elem: Element,
+    rendered: &HashMap<&str, &str>,
+    out: &mut String,
+) -> Result<()> {
+    let name = elem.full_name(doc);
+    out.push('<');
+    out.push_str(name);
+
+    // Namespace declarations sorted by prefix (the default namespace comes first),
+    // without the ones which the output already declares.
+    let mut in_scope = rendered.clone();
+    let mut decls: Vec<(&str, &str)> = elem
+        .namespace_decls(doc)
+        .iter()
+        .map(|(prefix, value)| (prefix.as_str(), value.as_str()))
+        .collect();
+    decls.sort_unstable();
+    for (prefix, value) in decls {
+        let current = in_scope.get(prefix).copied().unwrap_or("");
+        let superfluous = if prefix.is_empty() {
+            current == value
+        } else {
+            in_scope.get(prefix) == Some(&value)
+        };
+        if superfluous || prefix == "xml" {
+            continue;
+        }
+        if prefix.is_empty() {
+            out.push_str(" xmlns=\"");
+        } else {
+            out.push_str(" xmlns:");
+            out.push_str(prefix);
+            out.push_str("=\"");
+        }
+        escape_attribute(value, out);
+        out.push('"');
+        in_scope.insert(prefix, value);
+    }
+
+    // Attributes sorted by namespace and then local name. Attributes without
+    // a prefix have no namespace and come first.
+    let mut attributes = Vec::new();
+    for (full_name, value) in elem.attributes(doc) {
+        let (prefix, local_name) = Element::separate_prefix_name(full_name);
+        let namespace = if prefix.is_empty() {
+            ""
+        } else {
+            elem.namespace_for_prefix(doc, prefix)
+                .ok_or_else(|| Error::UndeclaredPrefix(prefix.to_string()))?
+        };
+        attributes.push(((namespace, local_name), full_name, value));
+    }
+    attributes.sort_unstable();
+    for (_, full_name, value) in attributes {
+        out.push(' ');
+        out.push_str(full_name);
+        out.push_str("=\"");
+        escape_attribute(value, out);
+        out.push('"');
+    }
+    out.push('>');
+
+    for node in elem.children(doc) {
+        match node {
+            Node::Element(child) => write_element(doc, *child, &in_scope, out)?,
+            Node::Text(text) | Node::CData(text) => escape_text(text, out),
+            Node::RawText(text) => escape_text(&unescape(text)?, out),
+            Node::PI(text) => {
+                check_node_text(node)?;
+                write_pi(text, out);
+            }
+            Node::Comment(_) | Node::DocType(_) => (),
+        }
+    }
+
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+    Ok(())
+}
+
+fn write_pi(text: &str, out: &mut String) {
+    out.push_str("<?");
+    out.push_str(text.trim_end());
+    out.push_str("?>");
+}
+
+fn escape_text(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape_attribute(value: &str, out: &mut String) {
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' => out.push_str("&#x9;"),
+            '\n' => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
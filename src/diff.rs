@@ -0,0 +1,168 @@
+use crate::document::{Document, Node};
+use crate::element::Element;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A single difference between two documents, as reported by [`Document::diff`].
+///
+/// Every difference is keyed by a `path`: the child indices leading to the node, starting
+/// with the index among the root nodes of the document (see [`Document::root_nodes`]).
+/// For example, `[0, 2]` is the third child of the first root node. Indices count all nodes,
+/// including text and comments. The path always refers to the position of the node
+/// in the document where the node exists (i.e. the other document for
+/// [`DocDiff::Added`]), but since children are compared by position, it is the same
+/// in both documents for all other differences.
+///
+/// The [`fmt::Display`] implementation writes the path as `/0/2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocDiff {
+    /// The node at `path` only exists in the other document.
+    Added { path: Vec<usize> },
+    /// The node at `path` only exists in this document.
+    Removed { path: Vec<usize> },
+    /// The attribute `name` of the element at `path` differs. The value is `None` if the
+    /// attribute is missing in the respective document. Namespace declarations are reported
+    /// as attributes named `xmlns` or `xmlns:prefix`.
+    AttributeChanged {
+        path: Vec<usize>,
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// The content of the non-element node at `path` differs.
+    TextChanged {
+        path: Vec<usize>,
+        old: String,
+        new: String,
+    },
+}
+
+impl DocDiff {
+    /// The path of the node which is affected by this difference.
+    pub fn path(&self) -> &[usize] {
+        match self {
+            DocDiff::Added { path }
+            | DocDiff::Removed { path }
+            | DocDiff::AttributeChanged { path, .. }
+            | DocDiff::TextChanged { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for DocDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for index in self.path() {
+            write!(f, "/{}", index)?;
+        }
+        match self {
+            DocDiff::Added { .. } => write!(f, ": added"),
+            DocDiff::Removed { .. } => write!(f, ": removed"),
+            DocDiff::AttributeChanged { name, old, new, .. } => {
+                write!(
+                    f,
+                    ": attribute `{}` changed from {:?} to {:?}",
+                    name, old, new
+                )
+            }
+            DocDiff::TextChanged { old, new, .. } => {
+                write!(f, ": text changed from {:?} to {:?}", old, new)
+            }
+        }
+    }
+}
+
+/// Compare two lists of sibling nodes by position and push the differences to `result`.
+pub(crate) fn diff_nodes(
+    path: &mut Vec<usize>,
+    (doc_a, nodes_a): (&Document, &[Node]),
+    (doc_b, nodes_b): (&Document, &[Node]),
+    result: &mut Vec<DocDiff>,
+) {
+    for i in 0..nodes_a.len().max(nodes_b.len()) {
+        path.push(i);
+        match (nodes_a.get(i), nodes_b.get(i)) {
+            (Some(a), Some(b)) => diff_node(path, (doc_a, a), (doc_b, b), result),
+            (Some(_), None) => result.push(DocDiff::Removed { path: path.clone() }),
+            (None, Some(_)) => result.push(DocDiff::Added { path: path.clone() }),
+            (None, None) => unreachable!(),
+        }
+        path.pop();
+    }
+}
+
+fn diff_node(
+    path: &mut Vec<usize>,
+    (doc_a, a): (&Document, &Node),
+    (doc_b, b): (&Document, &Node),
+    result: &mut Vec<DocDiff>,
+) {
+    let (text_a, text_b) = match (a, b) {
+        (Node::Element(a), Node::Element(b)) if a.full_name(doc_a) == b.full_name(doc_b) => {
+            diff_attributes(path, (doc_a, *a), (doc_b, *b), result);
+            diff_nodes(
+                path,
+                (doc_a, a.children(doc_a)),
+                (doc_b, b.children(doc_b)),
+                result,
+            );
+            return;
+        }
+        (Node::Text(a), Node::Text(b))
+        | (Node::Comment(a), Node::Comment(b))
+        | (Node::CData(a), Node::CData(b))
+        | (Node::PI(a), Node::PI(b))
+        | (Node::DocType(a), Node::DocType(b))
+        | (Node::RawText(a), Node::RawText(b)) => (a, b),
+        _ => {
+            // Different kinds of nodes, or elements with different names.
+            result.push(DocDiff::Removed { path: path.clone() });
+            result.push(DocDiff::Added { path: path.clone() });
+            return;
+        }
+    };
+    if text_a != text_b {
+        result.push(DocDiff::TextChanged {
+            path: path.clone(),
+            old: text_a.clone(),
+            new: text_b.clone(),
+        });
+    }
+}
+
+fn diff_attributes(
+    path: &[usize],
+    (doc_a, a): (&Document, Element),
+    (doc_b, b): (&Document, Element),
+    result: &mut Vec<DocDiff>,
+) {
+    fn decl_name(prefix: &str) -> String {
+        if prefix.is_empty() {
+            "xmlns".to_string()
+        } else {
+            format!("xmlns:{}", prefix)
+        }
+    }
+
+    let mut push = |name: String, old: Option<&String>, new: Option<&String>| {
+        if old != new {
+            result.push(DocDiff::AttributeChanged {
+                path: path.to_vec(),
+                name,
+                old: old.cloned(),
+                new: new.cloned(),
+            });
+        }
+    };
+
+    let (attrs_a, attrs_b) = (a.attributes(doc_a), b.attributes(doc_b));
+    let names: BTreeSet<&String> = attrs_a.keys().chain(attrs_b.keys()).collect();
+    for name in names {
+        push(name.clone(), attrs_a.get(name), attrs_b.get(name));
+    }
+
+    let (decls_a, decls_b) = (a.namespace_decls(doc_a), b.namespace_decls(doc_b));
+    let prefixes: BTreeSet<&String> = decls_a.keys().chain(decls_b.keys()).collect();
+    for prefix in prefixes {
+        push(decl_name(prefix), decls_a.get(prefix), decls_b.get(prefix));
+    }
+}
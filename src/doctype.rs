@@ -0,0 +1,125 @@
+/// Information extracted from a document type declaration. See [`parse_doctype`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DocTypeInfo {
+    /// Name of the root element declared by the doctype.
+    pub name: String,
+    /// `<!ENTITY ...>` declarations of the internal subset, in declaration order.
+    pub entities: Vec<EntityDecl>,
+}
+
+/// An `<!ENTITY ...>` declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityDecl {
+    /// Name of the entity.
+    pub name: String,
+    /// Replacement text of an internal entity (`<!ENTITY name "value">`),
+    /// or `None` for an external entity (`<!ENTITY name SYSTEM "uri">`).
+    pub value: Option<String>,
+    /// `true` for a parameter entity (`<!ENTITY % name "value">`).
+    pub is_parameter: bool,
+}
+
+/// Parse the content of a [`crate::Node::DocType`], i.e. the text between `<!DOCTYPE` and `>`.
+///
+/// This is not a DTD validator: only the root element name and the entity declarations
+/// of the internal subset are extracted. Other declarations and comments are skipped.
+///
+/// ```rust
+/// use biodivine_xml_doc::{parse_doctype, Document};
+///
+/// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+/// <!DOCTYPE book [
+///     <!ENTITY product "Widget">
+///     <!ENTITY logo SYSTEM "logo.png">
+/// ]>
+/// <book/>
+/// "#).unwrap();
+///
+/// let info = parse_doctype(doc.doctype().unwrap());
+/// assert_eq!(info.name, "book");
+/// assert_eq!(info.entities.len(), 2);
+/// assert_eq!(info.entities[0].name, "product");
+/// assert_eq!(info.entities[0].value.as_deref(), Some("Widget"));
+/// assert_eq!(info.entities[1].value, None);
+/// ```
+pub fn parse_doctype(doctype: &str) -> DocTypeInfo {
+    let doctype = doctype.trim_start();
+    let name_end = doctype
+        .find(|c: char| c.is_whitespace() || c == '[')
+        .unwrap_or(doctype.len());
+    let name = doctype[..name_end].to_string();
+
+    let mut entities = Vec::new();
+    if let Some(subset_start) = doctype.find('[') {
+        let mut rest = &doctype[subset_start + 1..];
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start..];
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = match comment.find("-->") {
+                    Some(end) => &comment[end + 3..],
+                    None => "",
+                };
+            } else if let Some(decl) = rest.strip_prefix("<!ENTITY") {
+                let (entity, remaining) = parse_entity_decl(decl);
+                entities.extend(entity);
+                rest = remaining;
+            } else {
+                rest = skip_markup_decl(&rest[1..]);
+            }
+        }
+    }
+    DocTypeInfo { name, entities }
+}
+
+/// Parse the rest of an `<!ENTITY` declaration. Returns the declaration (if it is valid)
+/// and the text after the declaration.
+fn parse_entity_decl(decl: &str) -> (Option<EntityDecl>, &str) {
+    let mut rest = decl.trim_start();
+    let mut is_parameter = false;
+    if let Some(after) = rest.strip_prefix('%') {
+        is_parameter = true;
+        rest = after.trim_start();
+    }
+    let name_end = rest
+        .find(|c: char| c.is_whitespace() || c == '>')
+        .unwrap_or(rest.len());
+    let name = rest[..name_end].to_string();
+    rest = rest[name_end..].trim_start();
+
+    let value = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => match rest[1..].find(quote) {
+            Some(end) => {
+                let value = rest[1..end + 1].to_string();
+                rest = &rest[end + 2..];
+                Some(value)
+            }
+            None => return (None, ""),
+        },
+        _ => None,
+    };
+    let rest = skip_markup_decl(rest);
+    if name.is_empty() {
+        return (None, rest);
+    }
+    let entity = EntityDecl {
+        name,
+        value,
+        is_parameter,
+    };
+    (Some(entity), rest)
+}
+
+/// Skip to the end of a markup declaration, ignoring `>` in quoted literals.
+fn skip_markup_decl(decl: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in decl.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return &decl[i + 1..],
+            None => {}
+        }
+    }
+    ""
+}
@@ -1,17 +1,45 @@
-use crate::element::{Element, ElementData};
+use crate::c14n;
+use crate::diff::{diff_nodes, DocDiff};
+use crate::element::{check_node_text, is_valid_qualified_name, Element, ElementData};
 use crate::error::{Error, Result};
-use crate::parser::{DocumentParser, ReadOptions};
+use crate::parser::{DocumentParser, ReadOptions, XmlEvents};
+use crate::value::{elements_to_map, value_to_elements, XmlValue};
+use crate::visitor::Visitor;
+use encoding_rs::{CoderResult, Encoder, Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use quick_xml::escape::{escape, unescape};
 use quick_xml::events::{BytesCData, BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Write};
 use std::iter::FromIterator;
 use std::path::Path;
 use std::str::FromStr;
 
 /// Represents an XML node.
-#[derive(Debug)]
+///
+/// Cloning a `Node::Element` only copies the [`Element`] handle, so the clone refers to the
+/// same element and does not copy its attributes or children. Since an element can only
+/// have one parent, pushing the clone into another element fails with [`Error::HasAParent`]
+/// as long as the original node is still attached.
+///
+/// ```rust
+/// use biodivine_xml_doc::{Document, Element, Error, Node};
+///
+/// let mut doc = Document::new();
+/// let a = Element::new(&mut doc, "a");
+/// let b = Element::new(&mut doc, "b");
+/// let separator = Node::Text(", ".to_string());
+/// a.push_child(&mut doc, separator.clone()).unwrap();
+/// b.push_child(&mut doc, separator).unwrap();
+///
+/// let item = Element::new(&mut doc, "item").as_node();
+/// a.push_child(&mut doc, item.clone()).unwrap();
+/// assert!(matches!(b.push_child(&mut doc, item), Err(Error::HasAParent)));
+/// ```
+#[derive(Debug, Clone)]
 pub enum Node {
     /// XML Element
     Element(Element),
@@ -25,6 +53,12 @@ pub enum Node {
     PI(String),
     /// Document Type Declaration ([specification](https://www.w3.org/TR/xml/#sec-prolog-dtd))
     DocType(String),
+    /// Character data which is already escaped, and is written verbatim, without escaping.
+    ///
+    /// It is the caller's responsibility to keep the content well-formed. The parser only
+    /// produces this node if [`ReadOptions::keep_raw_text`] is set, otherwise the content is
+    /// read back as [`Node::Text`] (or as other nodes, if it contains markup).
+    RawText(String),
 }
 
 impl Node {
@@ -55,17 +89,102 @@ impl Node {
         }
     }
 
+    /// Returns the text if node is `Text`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <config>Text<!--Comment--></config>
+    /// "#).unwrap();
+    ///
+    /// let children = doc.root_element().unwrap().children(&doc);
+    /// assert_eq!(children[0].as_text(), Some("Text"));
+    /// assert_eq!(children[1].as_text(), None);
+    /// assert_eq!(children[1].as_comment(), Some("Comment"));
+    /// ```
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the content if node is `Comment`.
+    pub fn as_comment(&self) -> Option<&str> {
+        match self {
+            Self::Comment(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the content if node is `CData`.
+    pub fn as_cdata(&self) -> Option<&str> {
+        match self {
+            Self::CData(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the content if node is `PI`.
+    pub fn as_pi(&self) -> Option<&str> {
+        match self {
+            Self::PI(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Turn a `Text` node into a `CData` node with the same content.
+    /// Other nodes are returned unchanged.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Node;
+    ///
+    /// let node = Node::Text("<&>".to_string()).text_to_cdata();
+    /// assert_eq!(node.as_cdata(), Some("<&>"));
+    /// let node = node.cdata_to_text();
+    /// assert_eq!(node.as_text(), Some("<&>"));
+    /// ```
+    pub fn text_to_cdata(self) -> Node {
+        match self {
+            Self::Text(text) => Self::CData(text),
+            node => node,
+        }
+    }
+
+    /// Turn a `CData` node into a `Text` node with the same content.
+    /// Other nodes are returned unchanged.
+    pub fn cdata_to_text(self) -> Node {
+        match self {
+            Self::CData(text) => Self::Text(text),
+            node => node,
+        }
+    }
+
+    /// Copy this node from `src` into `dst`. Elements are copied
+    /// with all their descendants, see [`Element::deep_copy`].
+    pub(crate) fn deep_copy(&self, src: &Document, dst: &mut Document) -> Node {
+        match self {
+            Node::Element(elem) => Node::Element(elem.deep_copy(src, dst)),
+            node => node.clone(),
+        }
+    }
+
     pub(crate) fn build_text_content<'a>(&self, doc: &'a Document, buf: &'a mut String) {
         match self {
             Node::Element(elem) => elem.build_text_content(doc, buf),
             Node::Text(text) => buf.push_str(text),
             Node::CData(text) => buf.push_str(text),
             Node::PI(text) => buf.push_str(text),
+            Node::RawText(text) => match unescape(text) {
+                Ok(text) => buf.push_str(&text),
+                Err(_) => buf.push_str(text),
+            },
             _ => {}
         }
     }
 
-    /// Returns content if node is `Text`, `CData`, or `PI`.
+    /// Returns content if node is `Text`, `CData`, or `PI`, and unescaped content if node is `RawText`.
     /// If node is `Element`, return [Element::text_content()]
     ///
     /// Implementation of [Node.textContent](https://developer.mozilla.org/en-US/docs/Web/API/Node/textContent)
@@ -76,6 +195,39 @@ impl Node {
     }
 }
 
+/// Creates a [`Node::Text`].
+///
+/// ```rust
+/// use biodivine_xml_doc::{Document, Element};
+///
+/// let mut doc = Document::new();
+/// let root = Element::new(&mut doc, "root");
+/// let child = Element::new(&mut doc, "child");
+/// root.push_child(&mut doc, "hello ").unwrap();
+/// root.push_child(&mut doc, child).unwrap();
+/// assert_eq!(root.text_content(&doc), "hello ");
+/// assert_eq!(root.child_elements(&doc), vec![child]);
+/// ```
+impl From<&str> for Node {
+    fn from(text: &str) -> Self {
+        Node::Text(text.to_string())
+    }
+}
+
+/// Creates a [`Node::Text`].
+impl From<String> for Node {
+    fn from(text: String) -> Self {
+        Node::Text(text)
+    }
+}
+
+/// Creates a [`Node::Element`], same as [`Element::as_node`].
+impl From<Element> for Node {
+    fn from(element: Element) -> Self {
+        Node::Element(element)
+    }
+}
+
 /// Represents a XML document or a document fragment.
 ///
 /// To build a document from scratch, use [`Document::new`].
@@ -106,74 +258,1054 @@ impl Node {
 /// let xml = doc.write_str();
 /// ```
 ///
-
-#[derive(Debug)]
+/// Cloning a document creates a deep copy. Since [`Element`] is only an index into the document,
+/// element handles of the original document remain valid in the copy and refer to the
+/// corresponding copied elements.
+#[derive(Debug, Clone)]
 pub struct Document {
     pub(crate) counter: usize, // == self.store.len()
     pub(crate) store: Vec<ElementData>,
     container: Element,
 
     pub(crate) version: String,
-    pub(crate) standalone: bool,
+    pub(crate) encoding: Option<String>,
+    pub(crate) standalone: Option<bool>,
+    // Encoding of the byte order mark of the parsed document.
+    pub(crate) bom: Option<&'static Encoding>,
+    // The parsed document has an XML declaration (always `true` for new documents).
+    pub(crate) declaration: bool,
+    // Attributes used by `get_element_by_id`, in order of precedence.
+    id_attributes: Vec<String>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Document::new()
+    }
 }
 
-impl Default for Document {
-    fn default() -> Self {
-        Document::new()
-    }
-}
+impl Document {
+    /// Create a blank new xml document.
+    pub fn new() -> Document {
+        let (container, container_data) = Element::container();
+        Document {
+            counter: 1, // because container is id 0
+            store: vec![container_data],
+            container,
+            version: String::from("1.0"),
+            encoding: Some(String::from("UTF-8")),
+            standalone: None,
+            bom: None,
+            declaration: true,
+            id_attributes: vec![String::from("xml:id"), String::from("id")],
+        }
+    }
+
+    /// Create a blank new xml document with pre-allocated storage for `elements` elements.
+    ///
+    /// This avoids re-allocations when the number of elements is known in advance,
+    /// e.g. when building a large document. See also [`ReadOptions::expected_elements`].
+    pub fn with_capacity(elements: usize) -> Document {
+        let mut doc = Document::new();
+        doc.store.reserve(elements);
+        doc
+    }
+
+    /// Get 'container' element of Document.
+    ///
+    /// The document uses an invisible 'container' element
+    /// which it uses to manage its root nodes.
+    ///
+    /// Its parent is None, and trying to change its parent will
+    /// return [`Error::ContainerCannotMove`].
+    ///
+    /// For the container element, only its `children` is relevant.
+    /// Other attributes are not used.
+    pub fn container(&self) -> Element {
+        self.container
+    }
+
+    /// Returns `true` if document doesn't have any nodes.
+    /// Returns `false` if you added a node or parsed an xml.
+    ///
+    /// Use [`Document::clear`] to make a document empty again.
+    pub fn is_empty(&self) -> bool {
+        self.store.len() == 1
+    }
+
+    /// Number of elements stored in this document, excluding the container.
+    ///
+    /// This is an `O(1)` operation. Note that this also counts elements which are
+    /// not attached to the document tree (e.g. created by [`Element::new`] but not pushed
+    /// anywhere, or detached by [`Element::detatch`]).
+    pub fn element_count(&self) -> usize {
+        self.counter - 1
+    }
+
+    /// Count the contents of the element store and estimate its memory usage,
+    /// e.g. to decide whether [`Document::compact`] is worthwhile.
+    /// Like [`Document::element_count`], this includes elements which are not attached
+    /// to the document tree. This scans the whole store.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root xmlns:a="http://a"><a:item id="1">text</a:item><a:item id="2"/></root>
+    /// "#).unwrap();
+    /// let report = doc.memory_report();
+    /// assert_eq!(report.elements, 3);
+    /// assert_eq!(report.attributes, 2);
+    /// assert_eq!(report.namespace_decls, 1);
+    /// assert_eq!(report.text_bytes, 4);
+    /// assert!(report.heap_bytes > 0);
+    /// println!("{:?}", report);
+    /// ```
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport {
+            elements: self.element_count(),
+            heap_bytes: self.store.capacity() * std::mem::size_of::<ElementData>(),
+            ..MemoryReport::default()
+        };
+        let mut names = HashSet::new();
+        for data in &self.store {
+            data.add_to_report(&mut report, &mut names);
+        }
+        report
+    }
+
+    /// Number of nodes in the document tree, i.e. all nodes reachable from the container
+    /// (excluding the container itself).
+    ///
+    /// This requires a traversal of the whole tree, so it is an `O(n)` operation.
+    /// Unlike [`Document::element_count`], detached elements are not counted.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root>
+    ///     <a>Text</a>
+    ///     <!-- comment -->
+    ///     <b/>
+    /// </root>
+    /// "#).unwrap();
+    /// Element::new(&mut doc, "detached");
+    /// assert_eq!(doc.element_count(), 4);
+    /// // root, a, text, comment, b
+    /// assert_eq!(doc.node_count(), 5);
+    /// ```
+    pub fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![self.container];
+        while let Some(elem) = stack.pop() {
+            let children = elem.children(self);
+            count += children.len();
+            stack.extend(children.iter().filter_map(|node| node.as_element()));
+        }
+        count
+    }
+
+    /// Remove all elements which are not reachable from [`Document::container`] (i.e. detached
+    /// elements and their descendants) from the element storage of the document, and release
+    /// the unused memory.
+    ///
+    /// **This invalidates all [`Element`] handles of the document**, since the remaining
+    /// elements are re-numbered (in document order). Using an old handle afterwards refers
+    /// to a different element, or panics. Acquire new handles after compacting,
+    /// e.g. through [`Document::root_element`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a/><b><c/></b></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let b = root.find(&doc, "b").unwrap();
+    /// b.detatch(&mut doc).unwrap();
+    /// assert_eq!(doc.element_count(), 4);
+    ///
+    /// doc.compact();
+    /// assert_eq!(doc.element_count(), 2);
+    /// // `root` and `b` must not be used anymore.
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.outer_xml(&doc).unwrap(), "<root>\n  <a/>\n</root>");
+    /// ```
+    pub fn compact(&mut self) {
+        let mut elements = vec![self.container];
+        elements.append(&mut self.container.child_elements_recursive(self));
+        Element::rebuild_store(self, &elements);
+        self.store.shrink_to_fit();
+    }
+
+    /// Remove all nodes and elements from this document, and reset the XML declaration
+    /// to that of [`Document::new`]. The allocated storage is kept, so the document can be
+    /// reused, e.g. by [`Document::read_str`], without allocating a fresh one.
+    ///
+    /// **All existing [`Element`] handles of this document become invalid.** Using them
+    /// afterwards can panic or silently refer to unrelated new elements.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.1" encoding="UTF-8"?>
+    /// <root><a/></root>
+    /// "#).unwrap();
+    /// doc.clear();
+    /// assert!(doc.is_empty());
+    /// assert!(doc.root_nodes().is_empty());
+    /// assert_eq!(doc.version(), "1.0");
+    /// ```
+    pub fn clear(&mut self) {
+        let (container, container_data) = Element::container();
+        self.store.clear();
+        self.store.push(container_data);
+        self.counter = 1; // because container is id 0
+        self.container = container;
+        self.version = String::from("1.0");
+        self.encoding = Some(String::from("UTF-8"));
+        self.standalone = None;
+        self.bom = None;
+        self.declaration = true;
+    }
+
+    /// Get all elements which have no parent, except for the container. These are the roots
+    /// of fragments which are not attached to the document tree (e.g. created by
+    /// [`Element::new`] but not pushed anywhere, or detached by [`Element::detatch`]),
+    /// but are still stored in the document. They can be re-attached,
+    /// or removed with [`Document::compact`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a><b/></a></root>
+    /// "#).unwrap();
+    /// assert!(doc.detached_roots().is_empty());
+    ///
+    /// let a = doc.root_element().unwrap().find(&doc, "a").unwrap();
+    /// a.detatch(&mut doc).unwrap();
+    /// let new = Element::new(&mut doc, "new");
+    /// assert_eq!(doc.detached_roots(), vec![a, new]);
+    /// ```
+    pub fn detached_roots(&self) -> Vec<Element> {
+        Element::stored_elements(self)
+            .filter(|elem| !elem.has_parent(self))
+            .collect()
+    }
+
+    /// Name of the encoding given by the byte order mark (BOM) at the start of the parsed
+    /// document (`"UTF-8"`, `"UTF-16LE"` or `"UTF-16BE"`), or `None` if there was no BOM.
+    ///
+    /// The BOM determines the encoding of the document, even if the XML declaration declares
+    /// a different encoding (unless [`ReadOptions::enforce_encoding`] is set). When writing
+    /// UTF-8, the UTF-8 BOM is preserved. When writing UTF-16, a BOM is always written.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let bytes = b"\xEF\xBB\xBF<?xml version=\"1.0\" encoding=\"UTF-8\"?><root/>";
+    /// let doc = Document::parse_bytes(bytes).unwrap();
+    /// assert_eq!(doc.byte_order_mark(), Some("UTF-8"));
+    /// let mut written = Vec::new();
+    /// doc.write(&mut written).unwrap();
+    /// assert!(written.starts_with(b"\xEF\xBB\xBF<?xml"));
+    /// ```
+    pub fn byte_order_mark(&self) -> Option<&'static str> {
+        self.bom.map(|encoding| encoding.name())
+    }
+
+    /// Returns `true` if the parsed document starts with an XML declaration (`<?xml ...?>`).
+    /// New documents always have a declaration.
+    ///
+    /// By default, the declaration is only written if the document has one,
+    /// see [`WriteOptions::write_decl`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, ReadOptions};
+    ///
+    /// let opts = ReadOptions { require_decl: false, ..ReadOptions::default() };
+    /// let doc = Document::parse_str_with_opts("<root/>", opts).unwrap();
+    /// assert!(!doc.had_declaration());
+    /// assert_eq!(doc.write_str().unwrap(), "<root/>");
+    ///
+    /// assert!(Document::new().had_declaration());
+    /// ```
+    pub fn had_declaration(&self) -> bool {
+        self.declaration
+    }
+
+    /// XML version of the document declaration. (`"1.0"` for new documents)
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Set the XML version of the document declaration.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidVersion`]: The version is not `"1.0"` or `"1.1"`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::new();
+    /// doc.set_version("1.1").unwrap();
+    /// assert_eq!(doc.version(), "1.1");
+    /// assert!(doc.set_version("2.0").is_err());
+    /// assert_eq!(doc.version(), "1.1");
+    /// ```
+    pub fn set_version<S: Into<String>>(&mut self, version: S) -> Result<()> {
+        let version = version.into();
+        if version != "1.0" && version != "1.1" {
+            return Err(Error::InvalidVersion(version));
+        }
+        self.version = version;
+        Ok(())
+    }
+
+    /// The `standalone` value of the document declaration, or `None`
+    /// if the declaration does not specify it. (`None` for new documents)
+    pub fn standalone(&self) -> Option<bool> {
+        self.standalone
+    }
+
+    /// Set the `standalone` value of the document declaration.
+    /// Use `None` to omit it from the declaration.
+    pub fn set_standalone(&mut self, standalone: Option<bool>) {
+        self.standalone = standalone;
+    }
+
+    /// Get root nodes of document.
+    pub fn root_nodes(&self) -> &Vec<Node> {
+        self.container.children(self)
+    }
+
+    /// Get first root node that is an element.
+    pub fn root_element(&self) -> Option<Element> {
+        self.container.child_elements(self).first().copied()
+    }
+
+    /// Get the root nodes before the root element (e.g. a license comment or the doctype),
+    /// in document order. If there is no root element, these are all root nodes.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!-- license --><?style a?><root/><!-- end -->"#).unwrap();
+    /// let prolog = doc.prolog_nodes();
+    /// assert_eq!(prolog.len(), 2);
+    /// assert_eq!(prolog[0].as_comment(), Some(" license "));
+    /// assert_eq!(prolog[1].as_pi(), Some("style a"));
+    /// assert_eq!(doc.epilog_nodes()[0].as_comment(), Some(" end "));
+    /// ```
+    pub fn prolog_nodes(&self) -> &[Node] {
+        let nodes = self.root_nodes();
+        let end = nodes
+            .iter()
+            .position(|node| node.as_element().is_some())
+            .unwrap_or(nodes.len());
+        &nodes[..end]
+    }
+
+    /// Get the root nodes after the root element, in document order. If the document has
+    /// more than one root element, these are the nodes after the last one. If there is
+    /// no root element, this is empty. See [`Document::prolog_nodes`].
+    pub fn epilog_nodes(&self) -> &[Node] {
+        let nodes = self.root_nodes();
+        let start = nodes
+            .iter()
+            .rposition(|node| node.as_element().is_some())
+            .map_or(nodes.len(), |index| index + 1);
+        &nodes[start..]
+    }
+
+    /// Get all root nodes that are elements, in document order.
+    ///
+    /// A well-formed XML document has exactly one root element, but a [`Document`] can have
+    /// more (e.g. after [`Document::append_document`]).
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!-- first --><a/>"#).unwrap();
+    /// let other = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?><b/>"#).unwrap();
+    /// doc.append_document(other);
+    /// let names: Vec<&str> = doc.root_elements().iter().map(|e| e.name(&doc)).collect();
+    /// assert_eq!(names, vec!["a", "b"]);
+    /// ```
+    pub fn root_elements(&self) -> Vec<Element> {
+        self.container.child_elements(self)
+    }
+
+    /// Check that the document tree can be written as a well-formed (and namespace
+    /// well-formed) XML document, and return all problems that were found.
+    ///
+    /// Since neither the parser nor the methods modifying the document enforce all rules,
+    /// a document can be invalid, e.g. after [`Document::append_document`] or when
+    /// attributes are inserted through [`Element::mut_attributes`]. This checks that:
+    /// - There is exactly one root element ([`Error::NoRootElement`],
+    ///   [`Error::MultipleRootElements`]).
+    /// - Element names, attribute names and namespace prefixes are valid
+    ///   ([`Error::InvalidName`]).
+    /// - No element has two attributes with the same local name and namespace
+    ///   ([`Error::DuplicateAttribute`]).
+    /// - All used prefixes are declared ([`Error::UndeclaredPrefix`]).
+    /// - Comments and processing instructions can be written ([`Error::InvalidComment`],
+    ///   [`Error::InvalidProcessingInstruction`]).
+    ///
+    /// Elements which are not attached to the document tree are not checked.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element, Error, Node};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root xmlns:a="http://a" xmlns:b="http://a" a:x="1"/>
+    /// "#).unwrap();
+    /// assert!(doc.validate_well_formed().is_ok());
+    ///
+    /// let root = doc.root_element().unwrap();
+    /// root.mut_attributes(&mut doc).insert("b:x".to_string(), "2".to_string());
+    /// Element::build("c:item").push_to(&mut doc, root);
+    /// doc.push_root_node(Node::Comment("a -- b".to_string())).unwrap();
+    /// let errors = doc.validate_well_formed().unwrap_err();
+    /// assert_eq!(errors.len(), 3);
+    /// assert!(matches!(&errors[0], Error::InvalidComment(_)));
+    /// assert!(matches!(&errors[1], Error::DuplicateAttribute { attribute, .. } if attribute == "b:x"));
+    /// assert!(matches!(&errors[2], Error::UndeclaredPrefix(prefix) if prefix == "c"));
+    /// ```
+    pub fn validate_well_formed(&self) -> std::result::Result<(), Vec<Error>> {
+        fn check_nodes(nodes: &[Node], errors: &mut Vec<Error>) {
+            for node in nodes {
+                if let Err(error) = check_node_text(node) {
+                    errors.push(error);
+                }
+            }
+        }
+
+        let mut errors = Vec::new();
+        match self.root_elements().len() {
+            0 => errors.push(Error::NoRootElement),
+            1 => (),
+            _ => errors.push(Error::MultipleRootElements),
+        }
+        check_nodes(self.root_nodes(), &mut errors);
+        for elem in self.container.child_elements_recursive(self) {
+            let name = elem.full_name(self);
+            if !is_valid_qualified_name(name) {
+                errors.push(Error::InvalidName(name.to_string()));
+            }
+            let mut prefixes: Vec<&String> = elem.namespace_decls(self).keys().collect();
+            prefixes.sort_unstable();
+            for prefix in prefixes {
+                if !prefix.is_empty() && (prefix.contains(':') || !is_valid_qualified_name(prefix))
+                {
+                    errors.push(Error::InvalidName(prefix.clone()));
+                }
+            }
+            let mut seen = HashSet::new();
+            for (attribute, _) in elem.attributes_sorted(self) {
+                if !is_valid_qualified_name(attribute) {
+                    errors.push(Error::InvalidName(attribute.to_string()));
+                }
+                let (prefix, local_name) = Element::separate_prefix_name(attribute);
+                let namespace = if prefix.is_empty() {
+                    None
+                } else {
+                    match elem.namespace_for_prefix(self, prefix) {
+                        Some(namespace) => Some(namespace),
+                        // Reported as an undeclared prefix below.
+                        None => continue,
+                    }
+                };
+                if !seen.insert((namespace, local_name)) {
+                    errors.push(Error::DuplicateAttribute {
+                        element: name.to_string(),
+                        attribute: attribute.to_string(),
+                    });
+                }
+            }
+            check_nodes(elem.children(self), &mut errors);
+        }
+        for prefix in self.container.undeclared_prefixes(self) {
+            errors.push(Error::UndeclaredPrefix(prefix));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Find the first element in the whole document (in document order) whose name
+    /// (without namespace prefix) is `name`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <model>
+    ///     <listOfReactions>
+    ///         <reaction id="r1"><product/></reaction>
+    ///         <reaction id="r2"/>
+    ///     </listOfReactions>
+    ///     <reaction id="r3"/>
+    /// </model>
+    /// "#).unwrap();
+    ///
+    /// let first = doc.find("reaction").unwrap();
+    /// assert_eq!(first.attribute(&doc, "id"), Some("r1"));
+    /// let ids: Vec<&str> = doc
+    ///     .find_all("reaction")
+    ///     .iter()
+    ///     .map(|e| e.attribute(&doc, "id").unwrap())
+    ///     .collect();
+    /// assert_eq!(ids, vec!["r1", "r2", "r3"]);
+    /// assert!(doc.find("species").is_none());
+    /// ```
+    pub fn find(&self, name: &str) -> Option<Element> {
+        self.find_first(|elem| elem.name(self) == name)
+    }
+
+    /// Find the first element in the whole document (in document order) whose ID is `id`.
+    ///
+    /// The ID of an element is the value of the first attribute of
+    /// [`Document::id_attributes`] which the element has. By default, these are `xml:id`
+    /// and then `id`, so `xml:id` takes precedence if an element has both. Attribute types
+    /// declared in a DTD are not taken into account, but such attributes can be added
+    /// using [`Document::set_id_attributes`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root>
+    ///     <a id="x"/>
+    ///     <b xml:id="y" id="z"/>
+    ///     <c key="k"/>
+    /// </root>
+    /// "#).unwrap();
+    ///
+    /// assert_eq!(doc.get_element_by_id("x"), doc.find("a"));
+    /// assert_eq!(doc.get_element_by_id("y"), doc.find("b"));
+    /// assert_eq!(doc.get_element_by_id("z"), None);
+    /// assert_eq!(doc.get_element_by_id("k"), None);
+    ///
+    /// doc.set_id_attributes(vec!["key".to_string()]);
+    /// assert_eq!(doc.get_element_by_id("k"), doc.find("c"));
+    /// ```
+    pub fn get_element_by_id(&self, id: &str) -> Option<Element> {
+        self.find_first(|elem| {
+            self.id_attributes
+                .iter()
+                .find_map(|name| elem.attribute(self, name))
+                == Some(id)
+        })
+    }
+
+    /// Full names of the attributes used by [`Document::get_element_by_id`], in order
+    /// of precedence. (`["xml:id", "id"]` by default)
+    pub fn id_attributes(&self) -> &[String] {
+        &self.id_attributes
+    }
+
+    /// Set the full names of the attributes used by [`Document::get_element_by_id`],
+    /// in order of precedence.
+    pub fn set_id_attributes(&mut self, names: Vec<String>) {
+        self.id_attributes = names;
+    }
+
+    /// Find the first element in document order which satisfies `predicate`.
+    fn find_first<F: Fn(Element) -> bool>(&self, predicate: F) -> Option<Element> {
+        let mut stack: Vec<Element> = self.container.child_elements_iter(self).collect();
+        stack.reverse();
+        while let Some(elem) = stack.pop() {
+            if predicate(elem) {
+                return Some(elem);
+            }
+            let children = stack.len();
+            stack.extend(elem.child_elements_iter(self));
+            stack[children..].reverse();
+        }
+        None
+    }
+
+    /// Find all elements in the whole document whose name (without namespace prefix)
+    /// is `name`, in document order. See [`Document::find`].
+    pub fn find_all(&self, name: &str) -> Vec<Element> {
+        self.container
+            .child_elements_recursive(self)
+            .into_iter()
+            .filter(|e| e.name(self) == name)
+            .collect()
+    }
+
+    /// Iterate over all nodes of the document in document order (pre-order), each paired
+    /// with its parent element. Root nodes are paired with [`Document::container`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a>text</a><!--comment--></root>
+    /// "#).unwrap();
+    ///
+    /// let root = doc.root_element().unwrap();
+    /// let a = root.find(&doc, "a").unwrap();
+    /// let nodes: Vec<_> = doc.descendant_nodes().collect();
+    /// assert_eq!(nodes.len(), 4);
+    /// assert_eq!((nodes[0].0, nodes[0].1.as_element()), (doc.container(), Some(root)));
+    /// assert_eq!((nodes[1].0, nodes[1].1.as_element()), (root, Some(a)));
+    /// assert_eq!((nodes[2].0, nodes[2].1.as_text()), (a, Some("text")));
+    /// assert_eq!((nodes[3].0, nodes[3].1.as_comment()), (root, Some("comment")));
+    /// ```
+    pub fn descendant_nodes(&self) -> impl Iterator<Item = (Element, &Node)> {
+        let mut stack = vec![(self.container, self.root_nodes().iter())];
+        std::iter::from_fn(move || loop {
+            let (parent, nodes) = stack.last_mut()?;
+            let parent = *parent;
+            match nodes.next() {
+                Some(node) => {
+                    if let Node::Element(elem) = node {
+                        stack.push((*elem, elem.children(self).iter()));
+                    }
+                    return Some((parent, node));
+                }
+                None => {
+                    stack.pop();
+                }
+            }
+        })
+    }
+
+    /// Compare the positions of two elements in document order (pre-order traversal): returns
+    /// [`Ordering::Less`] if `a` precedes `b`, [`Ordering::Equal`] if they are the same element,
+    /// and [`Ordering::Greater`] if `a` follows `b`. An ancestor precedes its descendants.
+    ///
+    /// Elements which are not in the same tree (e.g. one of them is detached) have
+    /// no document order. Such elements are ordered by the order in which the roots
+    /// of their trees were created, which is arbitrary, but consistent.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    /// use std::cmp::Ordering;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a><b/></a><c/></root>
+    /// "#).unwrap();
+    /// let mut elements = vec![doc.find("c").unwrap(), doc.find("b").unwrap(), doc.find("a").unwrap()];
+    /// elements.sort_by(|x, y| doc.compare_order(*x, *y));
+    /// let names: Vec<&str> = elements.iter().map(|e| e.name(&doc)).collect();
+    /// assert_eq!(names, vec!["a", "b", "c"]);
+    /// assert_eq!(doc.compare_order(doc.container(), elements[0]), Ordering::Less);
+    /// ```
+    pub fn compare_order(&self, a: Element, b: Element) -> Ordering {
+        let path_a = self.ancestor_path(a);
+        let path_b = self.ancestor_path(b);
+        if path_a[0] != path_b[0] {
+            return path_a[0].cmp_store_position(&path_b[0]);
+        }
+        // Find the first position where the paths diverge.
+        let common = path_a
+            .iter()
+            .zip(path_b.iter())
+            .take_while(|(x, y)| x == y)
+            .count();
+        match (path_a.get(common), path_b.get(common)) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let parent = path_a[common - 1];
+                let index = |e: &Element| {
+                    parent
+                        .children(self)
+                        .iter()
+                        .position(|n| n.as_element() == Some(*e))
+                };
+                index(x).cmp(&index(y))
+            }
+        }
+    }
+
+    /// Find the deepest element which is an ancestor of both `a` and `b`.
+    ///
+    /// An element counts as its own ancestor here: if `a` is an ancestor of `b`, the result is `a`
+    /// (and `a` itself if both are the same element). For two elements of the document tree,
+    /// the result is at worst the [`Document::container`]. Returns `None` if `a` and `b`
+    /// are in different trees, e.g. when one of them is detached.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a><b/><c/></a><d/></root>
+    /// "#).unwrap();
+    /// let a = doc.find("a").unwrap();
+    /// let b = doc.find("b").unwrap();
+    /// let c = doc.find("c").unwrap();
+    /// let d = doc.find("d").unwrap();
+    /// assert_eq!(doc.common_ancestor(b, c), Some(a));
+    /// assert_eq!(doc.common_ancestor(a, b), Some(a));
+    /// assert_eq!(doc.common_ancestor(b, d), doc.root_element());
+    /// ```
+    pub fn common_ancestor(&self, a: Element, b: Element) -> Option<Element> {
+        let path_a = self.ancestor_path(a);
+        let path_b = self.ancestor_path(b);
+        path_a
+            .iter()
+            .zip(path_b.iter())
+            .take_while(|(x, y)| x == y)
+            .last()
+            .map(|(x, _)| *x)
+    }
+
+    /// The element and all its ancestors, starting with the top-most ancestor.
+    fn ancestor_path(&self, elem: Element) -> Vec<Element> {
+        let mut path = vec![elem];
+        let mut current = elem;
+        while let Some(parent) = current.parent(self) {
+            path.push(parent);
+            current = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Get the content of the first [`Node::DocType`] root node, if there is one.
+    ///
+    /// Use [`crate::parse_doctype`] to read the declared root name and entities.
+    pub fn doctype(&self) -> Option<&str> {
+        self.root_nodes().iter().find_map(|node| match node {
+            Node::DocType(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Push a node to end of root nodes.
+    /// If doc has no [`Element`], pushing a [`Node::Element`] is
+    /// equivalent to setting it as root element.
+    pub fn push_root_node(&mut self, node: impl Into<Node>) -> Result<()> {
+        let elem = self.container;
+        elem.push_child(self, node)
+    }
+
+    /// Replace the root node at `index` with `node`, and return the replaced node.
+    /// A replaced [`Node::Element`] is detached, see [`Element::replace_child`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.root_nodes().len()`.
+    ///
+    /// # Errors
+    /// - [`Error::HasAParent`]: `node` is an element which already has a parent.
+    /// - [`Error::ContainerCannotMove`]: `node` is the container.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Node};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!-- old --><root/>"#).unwrap();
+    /// let old = doc.replace_root_node(0, Node::Comment(" new ".to_string())).unwrap();
+    /// assert_eq!(old.as_comment(), Some(" old "));
+    /// assert_eq!(doc.root_nodes()[0].as_comment(), Some(" new "));
+    /// ```
+    pub fn replace_root_node(&mut self, index: usize, node: impl Into<Node>) -> Result<Node> {
+        let elem = self.container;
+        elem.replace_child(self, index, node)
+    }
+
+    /// Remove the root node at `index` and return it.
+    /// A removed [`Node::Element`] is detached, see [`Element::remove_child`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.root_nodes().len()`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!-- comment --><root/>"#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// doc.remove_root_node(0);
+    /// let removed = doc.remove_root_node(0);
+    /// assert_eq!(removed.as_element(), Some(root));
+    /// assert_eq!(root.parent(&doc), None);
+    /// assert!(doc.root_nodes().is_empty());
+    /// ```
+    pub fn remove_root_node(&mut self, index: usize) -> Node {
+        let elem = self.container;
+        elem.remove_child(self, index)
+    }
+
+    /// Move all root nodes of `other` to the end of the root nodes of this document.
+    ///
+    /// Since [`Element`] handles are only valid in the document which created them, the elements
+    /// of `other` are recreated in this document, together with all their descendants.
+    /// The XML declaration of `other` is discarded.
+    ///
+    /// Note that if both documents have a root element, the result has more than one
+    /// root element, and [`Document::root_element`] returns the first one. To merge
+    /// the trees instead, move the nodes under the root element yourself.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <report id="1"/>"#).unwrap();
+    /// let other = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!-- second --><report id="2"><item/></report>"#).unwrap();
+    /// doc.append_document(other);
+    ///
+    /// assert_eq!(doc.root_nodes().len(), 3);
+    /// assert_eq!(doc.root_nodes()[1].as_comment(), Some(" second "));
+    /// let second = doc.root_nodes()[2].as_element().unwrap();
+    /// assert_eq!(second.attribute(&doc, "id"), Some("2"));
+    /// assert_eq!(second.find(&doc, "item").unwrap().parent(&doc), Some(second));
+    /// ```
+    pub fn append_document(&mut self, other: Document) {
+        for node in other.root_nodes() {
+            let node = node.deep_copy(&other, self);
+            // The copy has no parent, so this cannot fail.
+            self.push_root_node(node).unwrap();
+        }
+    }
+
+    /// Move namespace declarations of the whole document to the root element, where possible.
+    ///
+    /// Every prefix declared anywhere in the tree of [`Document::root_element`] is declared
+    /// once on the root element and all re-declarations of the same url are then
+    /// removed (see [`Element::remove_redundant_namespace_decls`]).
+    ///
+    /// Conflicting bindings (the same prefix declared with different urls) are resolved as
+    /// follows: a declaration on the root element always wins, otherwise the first declaration
+    /// in document order is moved to the root element. All other bindings of that prefix
+    /// stay where they are, since they shadow the root declaration.
+    ///
+    /// The default namespace (empty prefix) is never moved, because it would change the
+    /// namespace of unprefixed elements outside of the declaring sub-tree. Similarly, a prefix
+    /// is not moved if it is used somewhere in the document without being declared.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root>
+    ///     <a:item xmlns:a="http://a"/>
+    ///     <a:item xmlns:a="http://a"/>
+    ///     <b:item xmlns:b="http://b">
+    ///         <a:item xmlns:a="http://other"/>
+    ///     </b:item>
+    /// </root>
+    /// "#).unwrap();
+    ///
+    /// doc.consolidate_namespaces();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.namespace_decls(&doc).len(), 2);
+    /// let items = root.child_elements(&doc);
+    /// assert!(items[0].namespace_decls(&doc).is_empty());
+    /// assert!(items[1].namespace_decls(&doc).is_empty());
+    /// assert!(items[2].namespace_decls(&doc).is_empty());
+    /// let inner = items[2].child_elements(&doc)[0];
+    /// assert_eq!(inner.namespace(&doc), Some("http://other"));
+    /// ```
+    pub fn consolidate_namespaces(&mut self) {
+        let Some(root) = self.root_element() else {
+            return;
+        };
+        let mut elements = vec![root];
+        elements.append(&mut root.child_elements_recursive(self));
+
+        // Prefixes which are used but not declared must not be declared by us.
+        let mut undeclared = HashSet::new();
+        for e in &elements {
+            let prefixes = std::iter::once(e.prefix(self)).chain(
+                e.attributes(self)
+                    .keys()
+                    .map(|k| Element::separate_prefix_name(k).0),
+            );
+            for prefix in prefixes {
+                if e.namespace_for_prefix(self, prefix).is_none() {
+                    undeclared.insert(prefix.to_string());
+                }
+            }
+        }
 
-impl Document {
-    /// Create a blank new xml document.
-    pub fn new() -> Document {
-        let (container, container_data) = Element::container();
-        Document {
-            counter: 1, // because container is id 0
-            store: vec![container_data],
-            container,
-            version: String::from("1.0"),
-            standalone: false,
+        let mut hoisted = HashMap::new();
+        for e in &elements {
+            for (prefix, url) in e.namespace_decls(self) {
+                if prefix.is_empty() || undeclared.contains(prefix) {
+                    continue;
+                }
+                if !hoisted.contains_key(prefix) {
+                    hoisted.insert(prefix.clone(), url.clone());
+                }
+            }
         }
+        for (prefix, url) in hoisted {
+            root.set_namespace_decl(self, prefix, url);
+        }
+        root.remove_redundant_namespace_decls(self);
     }
 
-    /// Get 'container' element of Document.
+    /// Compare this document with `other` and list the differences between the two trees.
     ///
-    /// The document uses an invisible 'container' element
-    /// which it uses to manage its root nodes.
+    /// The comparison is coarse: children are matched by their position, so inserting a node
+    /// makes all its following siblings differ as well. Nodes of different kinds, or elements
+    /// with different names, are reported as a [`DocDiff::Removed`] followed by
+    /// a [`DocDiff::Added`], without comparing their content. Otherwise, elements are compared
+    /// by attributes (including namespace declarations) and children, while other nodes are
+    /// compared by content. The XML declaration is not compared.
     ///
-    /// Its parent is None, and trying to change its parent will
-    /// return [`Error::ContainerCannotMove`].
+    /// ```rust
+    /// use biodivine_xml_doc::{DocDiff, Document};
     ///
-    /// For the container element, only its `children` is relevant.
-    /// Other attributes are not used.
-    pub fn container(&self) -> Element {
-        self.container
+    /// let a = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root id="1"><a>Hello</a><b/></root>"#).unwrap();
+    /// let b = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root id="2"><a>World</a><c/><d/></root>"#).unwrap();
+    ///
+    /// let diff: Vec<String> = a.diff(&b).iter().map(|d| d.to_string()).collect();
+    /// assert_eq!(diff, vec![
+    ///     r#"/0: attribute `id` changed from Some("1") to Some("2")"#,
+    ///     r#"/0/0/0: text changed from "Hello" to "World""#,
+    ///     "/0/1: removed",
+    ///     "/0/1: added",
+    ///     "/0/2: added",
+    /// ]);
+    /// assert_eq!(a.diff(&b)[4], DocDiff::Added { path: vec![0, 2] });
+    /// assert!(a.diff(&a).is_empty());
+    /// ```
+    pub fn diff(&self, other: &Document) -> Vec<DocDiff> {
+        let mut result = Vec::new();
+        diff_nodes(
+            &mut Vec::new(),
+            (self, self.root_nodes()),
+            (other, other.root_nodes()),
+            &mut result,
+        );
+        result
     }
 
-    /// Returns `true` if document doesn't have any nodes.
-    /// Returns `false` if you added a node or parsed an xml.
+    /// Convert the document into a simple nested [`XmlValue`], for reading data-oriented XML
+    /// (e.g. configuration files) without walking the tree. The result is a
+    /// [`XmlValue::Map`] with the root element under its full name.
     ///
-    /// You can only call `parse_*()` if document is empty.
-    pub fn is_empty(&self) -> bool {
-        self.store.len() == 1
+    /// The conversion is lossy by design, see [`XmlValue`] for the format:
+    /// - Comments, processing instructions and the document type are ignored.
+    /// - The order of child elements with different names is lost (only the order of
+    ///   elements with the same name is kept, as a [`XmlValue::List`]).
+    /// - For elements with mixed content, all direct text is concatenated and trimmed,
+    ///   so its position between child elements is lost.
+    /// - CDATA sections are treated as text.
+    /// - Whether an element is a single [`XmlValue::Text`] or a [`XmlValue::List`] depends
+    ///   on the document, so check both if an element may repeat.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, XmlValue};
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <config version="2">
+    ///     <name>main</name>
+    ///     <!-- ignored -->
+    ///     <server>alpha</server>
+    ///     <server>beta</server>
+    /// </config>
+    /// "#).unwrap();
+    ///
+    /// let value = doc.to_value();
+    /// let config = value.get("config").unwrap();
+    /// assert_eq!(config.get("@version").unwrap().as_text(), Some("2"));
+    /// assert_eq!(config.get("name"), Some(&XmlValue::Text("main".to_string())));
+    /// let servers: Vec<&str> = config
+    ///     .get("server")
+    ///     .and_then(|servers| servers.as_list())
+    ///     .unwrap()
+    ///     .iter()
+    ///     .filter_map(|server| server.as_text())
+    ///     .collect();
+    /// assert_eq!(servers, vec!["alpha", "beta"]);
+    /// ```
+    pub fn to_value(&self) -> XmlValue {
+        let mut map = BTreeMap::new();
+        elements_to_map(self, self.container.child_elements_iter(self), &mut map);
+        XmlValue::Map(map)
     }
 
-    /// Get root nodes of document.
-    pub fn root_nodes(&self) -> &Vec<Node> {
-        self.container.children(self)
+    /// Create a document from a [`XmlValue`], the inverse of [`Document::to_value`].
+    ///
+    /// `value` should be a [`XmlValue::Map`], whose entries become the root elements
+    /// (normally just one). Within the map of an element, keys starting with `@` become
+    /// attributes (or namespace declarations for `@xmlns` and `@xmlns:prefix`) and `#text`
+    /// becomes the first child text, while the other keys become child elements, in the order
+    /// of the map. A [`XmlValue::List`] creates one element for every item.
+    ///
+    /// Values which do not fit the format are ignored: a `value` which is not a map,
+    /// attributes or `#text` at the top level, and attributes or `#text` whose value
+    /// is not a [`XmlValue::Text`].
+    ///
+    /// # Errors
+    /// - [`Error::InvalidName`]: A key is not a valid element or attribute name.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, XmlValue};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let text = |text: &str| XmlValue::Text(text.to_string());
+    /// let server = XmlValue::Map(BTreeMap::from([
+    ///     ("@port".to_string(), text("8080")),
+    ///     ("host".to_string(), text("localhost")),
+    /// ]));
+    /// let config = XmlValue::Map(BTreeMap::from([
+    ///     ("server".to_string(), XmlValue::List(vec![server.clone(), server])),
+    ///     ("name".to_string(), text("main")),
+    /// ]));
+    /// let value = XmlValue::Map(BTreeMap::from([("config".to_string(), config)]));
+    ///
+    /// let doc = Document::from_value(&value).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.find_all(&doc, "server").len(), 2);
+    /// assert_eq!(root.find(&doc, "name").unwrap().text_content(&doc), "main");
+    /// assert_eq!(doc.to_value(), value);
+    /// ```
+    pub fn from_value(value: &XmlValue) -> Result<Document> {
+        let mut doc = Document::new();
+        if let XmlValue::Map(map) = value {
+            let container = doc.container();
+            for (name, value) in map {
+                if !name.starts_with(['@', '#']) {
+                    value_to_elements(&mut doc, container, name, value)?;
+                }
+            }
+        }
+        Ok(doc)
     }
 
-    /// Get first root node that is an element.
-    pub fn root_element(&self) -> Option<Element> {
-        self.container.child_elements(self).first().copied()
+    /// Walk the whole document tree in document order, calling the methods of `visitor`
+    /// for every node. See [`Visitor`] for details.
+    pub fn walk<V: Visitor>(&self, visitor: &mut V) {
+        self.walk_nodes(self.root_nodes(), visitor);
     }
 
-    /// Push a node to end of root nodes.
-    /// If doc has no [`Element`], pushing a [`Node::Element`] is
-    /// equivalent to setting it as root element.
-    pub fn push_root_node(&mut self, node: Node) -> Result<()> {
-        let elem = self.container;
-        elem.push_child(self, node)
+    fn walk_nodes<V: Visitor>(&self, nodes: &[Node], visitor: &mut V) {
+        for node in nodes {
+            match node {
+                Node::Element(elem) => {
+                    visitor.visit_element_start(*elem, self);
+                    self.walk_nodes(elem.children(self), visitor);
+                    visitor.visit_element_end(*elem, self);
+                }
+                Node::Text(text) => visitor.visit_text(text),
+                Node::Comment(text) => visitor.visit_comment(text),
+                Node::CData(text) => visitor.visit_cdata(text),
+                Node::PI(text) => visitor.visit_pi(text),
+                Node::DocType(text) => visitor.visit_doctype(text),
+                Node::RawText(text) => visitor.visit_raw_text(text),
+            }
+        }
     }
 }
 
@@ -191,28 +1323,166 @@ impl Document {
 /// - [`Error::MalformedXML`]: Could not read XML.
 /// - [`Error::Io`]: IO Error
 impl Document {
+    /// Parse a document from a string.
+    ///
+    /// Since the string is already decoded, the encoding in the XML declaration
+    /// is only recorded (and checked if requested by [`ReadOptions::enforce_encoding`]),
+    /// but it is not used for decoding.
     pub fn parse_str(str: &str) -> Result<Document> {
-        DocumentParser::parse_reader(str.as_bytes(), ReadOptions::default())
+        DocumentParser::parse_str(str, ReadOptions::default())
+    }
+    /// Same as [`Document::parse_str`]. The document can also be created using
+    /// [`str::parse`] or [`TryFrom`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let xml = r#"<?xml version="1.0" encoding="UTF-8"?><root/>"#;
+    /// let doc = Document::parse(xml).unwrap();
+    /// let doc2: Document = xml.parse().unwrap();
+    /// let doc3 = Document::try_from(xml.to_string()).unwrap();
+    /// assert_eq!(doc.to_string(), doc2.to_string());
+    /// assert_eq!(doc.to_string(), doc3.to_string());
+    /// ```
+    pub fn parse(str: &str) -> Result<Document> {
+        Document::parse_str(str)
     }
     pub fn parse_str_with_opts(str: &str, opts: ReadOptions) -> Result<Document> {
-        DocumentParser::parse_reader(str.as_bytes(), opts)
+        DocumentParser::parse_str(str, opts)
+    }
+
+    /// Replace the content of this document with the document parsed from `str`. Same as
+    /// [`Document::parse_str`], but reuses the storage of this document (see [`Document::clear`]),
+    /// which avoids allocations when parsing many small documents.
+    ///
+    /// All existing [`Element`] handles of this document become invalid. If parsing fails,
+    /// the document is left empty, as after [`Document::clear`]. In both cases, the
+    /// [`Document::id_attributes`] of this document are kept.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::new();
+    /// for i in 0..3 {
+    ///     let xml = format!(r#"<?xml version="1.0" encoding="UTF-8"?><item id="{}"/>"#, i);
+    ///     doc.read_str(&xml).unwrap();
+    ///     let id = i.to_string();
+    ///     assert_eq!(doc.root_element().unwrap().attribute(&doc, "id"), Some(id.as_str()));
+    ///     assert_eq!(doc.node_count(), 1);
+    /// }
+    /// ```
+    pub fn read_str(&mut self, str: &str) -> Result<()> {
+        self.read_str_with_opts(str, ReadOptions::default())
+    }
+
+    /// Same as [`Document::read_str`], but with custom [`ReadOptions`].
+    pub fn read_str_with_opts(&mut self, str: &str, opts: ReadOptions) -> Result<()> {
+        self.clear();
+        let result = DocumentParser::parse_str_into(self, str, opts);
+        if result.is_err() {
+            // Drop the partially parsed content.
+            self.clear();
+        }
+        result
+    }
+
+    /// Parse a document from bytes. Unlike [`Document::parse_str`], the bytes are decoded
+    /// using the encoding of the XML declaration (or the byte order mark).
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut bytes = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><name>".to_vec();
+    /// bytes.extend_from_slice(&[b'J', 0xFC, b'r', b'g', b'e', b'n']); // "Jürgen" in Latin-1
+    /// bytes.extend_from_slice(b"</name>");
+    /// let doc = Document::parse_bytes(&bytes).unwrap();
+    /// assert_eq!(doc.root_element().unwrap().text_content(&doc), "Jürgen");
+    /// ```
+    pub fn parse_bytes(bytes: &[u8]) -> Result<Document> {
+        Self::parse_bytes_with_opts(bytes, ReadOptions::default())
+    }
+    pub fn parse_bytes_with_opts(bytes: &[u8], opts: ReadOptions) -> Result<Document> {
+        DocumentParser::parse_reader(bytes, Some(bytes.len()), opts)
     }
 
     pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Document> {
-        let file = File::open(path)?;
-        DocumentParser::parse_reader(file, ReadOptions::default())
+        Self::parse_file_with_opts(path, ReadOptions::default())
     }
     pub fn parse_file_with_opts<P: AsRef<Path>>(path: P, opts: ReadOptions) -> Result<Document> {
         let file = File::open(path)?;
-        DocumentParser::parse_reader(file, opts)
+        let len = file.metadata().ok().map(|m| m.len() as usize);
+        DocumentParser::parse_reader(file, len, opts)
     }
 
     pub fn parse_reader<R: Read>(reader: R) -> Result<Document> {
-        DocumentParser::parse_reader(reader, ReadOptions::default())
+        DocumentParser::parse_reader(reader, None, ReadOptions::default())
     }
     pub fn parse_reader_with_opts<R: Read>(reader: R, opts: ReadOptions) -> Result<Document> {
-        DocumentParser::parse_reader(reader, opts)
+        DocumentParser::parse_reader(reader, None, opts)
     }
+
+    /// Same as [`Document::parse_reader_with_opts`], but calls `on_progress` with the number
+    /// of bytes consumed by the parser so far, e.g. to show a progress bar.
+    ///
+    /// `on_progress` is called every few hundred parsed events and once at the end of the
+    /// document, each time with a larger number. For documents which are not UTF-8, the
+    /// bytes are counted after decoding them to UTF-8, so the final number can differ from
+    /// the size of the input.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, ReadOptions};
+    ///
+    /// let xml = format!(
+    ///     r#"<?xml version="1.0" encoding="UTF-8"?><root>{}</root>"#,
+    ///     "<item/>".repeat(10_000),
+    /// );
+    /// let mut progress = Vec::new();
+    /// let doc = Document::parse_reader_with_progress(
+    ///     xml.as_bytes(),
+    ///     ReadOptions::default(),
+    ///     |bytes| progress.push(bytes),
+    /// ).unwrap();
+    /// assert_eq!(doc.node_count(), 10_001);
+    /// assert!(progress.len() > 1);
+    /// assert!(progress.windows(2).all(|w| w[0] < w[1]));
+    /// assert_eq!(progress.last(), Some(&xml.len()));
+    /// ```
+    pub fn parse_reader_with_progress<R, F>(
+        reader: R,
+        opts: ReadOptions,
+        on_progress: F,
+    ) -> Result<Document>
+    where
+        R: Read,
+        F: FnMut(usize),
+    {
+        DocumentParser::parse_reader_with_progress(reader, opts, on_progress)
+    }
+
+    /// Read the XML as a stream of [`crate::XmlEvent`]s, without building a document tree.
+    ///
+    /// The XML declaration is read eagerly, so encoding and declaration
+    /// errors are returned immediately. See [`XmlEvents`] for an example.
+    pub fn event_reader<R: Read>(reader: R, opts: ReadOptions) -> Result<XmlEvents<R>> {
+        XmlEvents::new(reader, opts)
+    }
+}
+
+/// Statistics about the contents of a document, see [`Document::memory_report`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    /// Number of stored elements, excluding the container.
+    pub elements: usize,
+    /// Number of attributes of all elements, not counting namespace declarations.
+    pub attributes: usize,
+    /// Number of namespace declarations of all elements.
+    pub namespace_decls: usize,
+    /// Length in bytes of the content of all non-element nodes (text, comments, etc.).
+    pub text_bytes: usize,
+    /// Approximate number of bytes allocated on the heap by the element store. Shared
+    /// element and attribute names are only counted once. The allocations of hash maps
+    /// are estimated from their capacity, so this is only a rough estimate.
+    pub heap_bytes: usize,
 }
 
 /// Options when writing XML.
@@ -221,8 +1491,134 @@ pub struct WriteOptions {
     pub indent_char: u8,
     /// How many indent_char should be used for indent. (default: 2)
     pub indent_size: usize,
-    /// XML declaration should be written at the top. (default: `true`)
-    pub write_decl: bool,
+    /// XML declaration should be written at the top. If `None`, the declaration is written
+    /// only if the document has one, see [`Document::had_declaration`]. (default: `None`)
+    pub write_decl: Option<bool>,
+    /// Encoding of the written document, overriding the encoding of the document
+    /// declaration. (default: `None`)
+    pub encoding: Option<String>,
+    /// How text and attribute values are escaped. (default: [`TextEscaping::Standard`])
+    pub text_escaping: TextEscaping,
+    /// Order in which the attributes of an element are written.
+    /// (default: [`AttributeOrder::Sorted`])
+    pub attribute_order: AttributeOrder,
+    /// Reproduce the layout of a document parsed with [`ReadOptions::preserve_formatting`]:
+    /// nothing is indented (`indent_char` and `indent_size` are ignored), attributes are
+    /// written in [`AttributeOrder::Source`], and elements which were written as
+    /// `<tag></tag>` keep this form (see [`Element::is_force_expanded`]), and the content of
+    /// `Node::DocType` (e.g. an internal subset) is written without escaping. Together with
+    /// [`WriteOptions::write_decl`] being `None`, writing an unmodified document
+    /// reproduces it, and modifications do not change the layout of the rest of the document.
+    ///
+    /// New elements are not indented, so you need to add whitespace text around them
+    /// yourself. Some details are still normalized: quotes around attribute values and in
+    /// the declaration, whitespace inside tags (e.g. `<a />` becomes `<a/>`), the position
+    /// of namespace declarations (written after the attributes), and escaping of text
+    /// according to `text_escaping` (use [`ReadOptions::keep_raw_text`] to keep the escaping
+    /// of the source).
+    /// (default: `false`)
+    pub preserve_formatting: bool,
+    /// Full names of elements which are always written as empty elements (e.g. `<br/>`),
+    /// such as the void elements of HTML. Their children are not written, even if
+    /// [`Element::is_force_expanded`] is set, so it is the caller's responsibility to make
+    /// sure that they have no meaningful children. This typically removes the empty
+    /// text of `<br></br>` parsed with [`ReadOptions::empty_text_node`].
+    /// (default: empty)
+    pub void_elements: HashSet<String>,
+    /// Only write elements up to this depth (see [`Element::depth`]), e.g. for a preview of
+    /// a large document. The children of an element at `max_depth` are replaced with
+    /// the comment `<!-- ... -->`. (default: `None`)
+    pub max_depth: Option<usize>,
+}
+
+/// Order of attributes when writing XML. See [`WriteOptions::attribute_order`].
+///
+/// Namespace declarations are always written after the attributes, sorted by prefix.
+///
+/// ```rust
+/// use biodivine_xml_doc::{AttributeOrder, Document, ReadOptions, WriteOptions};
+///
+/// let read_opts = ReadOptions {
+///     require_decl: false,
+///     ..ReadOptions::default()
+/// };
+/// let doc = Document::parse_str_with_opts(r#"<item name="a" id="1"/>"#, read_opts).unwrap();
+/// let write = |attribute_order| {
+///     let opts = WriteOptions {
+///         write_decl: Some(false),
+///         attribute_order,
+///         ..WriteOptions::default()
+///     };
+///     doc.write_str_with_opts(opts).unwrap()
+/// };
+/// assert_eq!(write(AttributeOrder::Sorted), r#"<item id="1" name="a"/>"#);
+/// assert_eq!(write(AttributeOrder::Source), r#"<item name="a" id="1"/>"#);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOrder {
+    /// Sort attributes by their full name, see [`Element::attributes_sorted`].
+    Sorted,
+    /// Keep the order of the parsed document. Attributes added by [`Element::set_attribute`]
+    /// are written after the existing ones, in the order in which they were added.
+    /// Attributes inserted directly through [`Element::mut_attributes`] come last, sorted.
+    Source,
+}
+
+/// Escaping of [`Node::Text`] content and attribute values when writing XML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEscaping {
+    /// Only escape what is required by the XML specification: `<` and `&`, `>` in `]]>`,
+    /// and `"` in attribute values.
+    Minimal,
+    /// Escape `<`, `>`, `&`, `'` and `"`.
+    Standard,
+    /// Same as `Standard`, but also escape all non-ASCII characters as character references.
+    AsciiOnly,
+}
+
+impl TextEscaping {
+    fn escape_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            TextEscaping::Minimal => {
+                if !text.contains(['<', '&']) && !text.contains("]]>") {
+                    return Cow::Borrowed(text);
+                }
+                let escaped = text.replace('&', "&amp;").replace('<', "&lt;");
+                Cow::Owned(escaped.replace("]]>", "]]&gt;"))
+            }
+            TextEscaping::Standard => escape(text),
+            TextEscaping::AsciiOnly => escape_non_ascii(escape(text)),
+        }
+    }
+
+    fn escape_attribute<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        match self {
+            TextEscaping::Minimal => {
+                if !value.contains(['<', '&', '"']) {
+                    return Cow::Borrowed(value);
+                }
+                let escaped = value.replace('&', "&amp;").replace('<', "&lt;");
+                Cow::Owned(escaped.replace('"', "&quot;"))
+            }
+            TextEscaping::Standard => escape(value),
+            TextEscaping::AsciiOnly => escape_non_ascii(escape(value)),
+        }
+    }
+}
+
+fn escape_non_ascii(text: Cow<str>) -> Cow<str> {
+    if text.is_ascii() {
+        return text;
+    }
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else {
+            escaped.push_str(&format!("&#x{:X};", c as u32));
+        }
+    }
+    Cow::Owned(escaped)
 }
 
 impl Default for WriteOptions {
@@ -230,8 +1626,110 @@ impl Default for WriteOptions {
         WriteOptions {
             indent_char: b' ',
             indent_size: 2,
-            write_decl: true,
+            write_decl: None,
+            encoding: None,
+            text_escaping: TextEscaping::Standard,
+            attribute_order: AttributeOrder::Sorted,
+            preserve_formatting: false,
+            void_elements: HashSet::new(),
+            max_depth: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    fn xml_writer<W: Write>(&self, writer: W) -> Writer<W> {
+        if self.preserve_formatting {
+            Writer::new(writer)
+        } else {
+            Writer::new_with_indent(writer, self.indent_char, self.indent_size)
+        }
+    }
+}
+
+/// Encodes the UTF-8 written into it and writes the result into `inner`.
+///
+/// Characters that cannot be encoded are written as character references.
+struct EncodingWriter<'a, W: Write> {
+    inner: &'a mut W,
+    /// `None` for UTF-16, which `encoding_rs` cannot encode into.
+    encoder: Option<Encoder>,
+    little_endian: bool,
+    /// Bytes of a UTF-8 sequence which is split between two writes.
+    incomplete: Vec<u8>,
+}
+
+impl<'a, W: Write> EncodingWriter<'a, W> {
+    fn new(inner: &'a mut W, encoder: Encoder) -> Self {
+        EncodingWriter {
+            inner,
+            encoder: Some(encoder),
+            little_endian: false,
+            incomplete: Vec::new(),
+        }
+    }
+
+    fn utf16(inner: &'a mut W, little_endian: bool) -> Self {
+        EncodingWriter {
+            inner,
+            encoder: None,
+            little_endian,
+            incomplete: Vec::new(),
+        }
+    }
+
+    fn encode(&mut self, mut text: &str, last: bool) -> std::io::Result<()> {
+        let Some(encoder) = &mut self.encoder else {
+            let mut bytes = Vec::with_capacity(2 * text.len());
+            for unit in text.encode_utf16() {
+                if self.little_endian {
+                    bytes.extend_from_slice(&unit.to_le_bytes());
+                } else {
+                    bytes.extend_from_slice(&unit.to_be_bytes());
+                }
+            }
+            return self.inner.write_all(&bytes);
+        };
+        let mut buf = [0u8; 4096];
+        loop {
+            let (result, read, written, _) = encoder.encode_from_utf8(text, &mut buf, last);
+            self.inner.write_all(&buf[..written])?;
+            text = &text[read..];
+            if let CoderResult::InputEmpty = result {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Finish the encoding. The written bytes must end with a complete UTF-8 sequence.
+    fn finish(mut self) -> Result<()> {
+        if !self.incomplete.is_empty() {
+            return Err(Error::CannotDecode);
         }
+        self.encode("", true)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut pending = std::mem::take(&mut self.incomplete);
+        pending.extend_from_slice(buf);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() => err.valid_up_to(),
+            Err(err) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err)),
+        };
+        // The prefix was just validated.
+        let text = std::str::from_utf8(&pending[..valid]).unwrap();
+        self.encode(text, false)?;
+        pending.drain(..valid);
+        self.incomplete = pending;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
     }
 }
 
@@ -239,14 +1737,34 @@ impl Default for WriteOptions {
 /// # Writing
 ///
 /// Below are methods for writing xml.
-/// The XML will be written in UTF-8.
+///
+/// The encoding declared in the XML declaration is the one of the parsed document, or UTF-8
+/// for new documents. It can be overridden using [`WriteOptions::encoding`].
+/// `write_str*` methods always return a (unicode) string, while the other methods
+/// encode the XML into the declared encoding. Characters that cannot be encoded
+/// are written as character references.
+///
+/// # Errors
+/// - [`Error::CannotDecode`]: The encoding is not recognized.
+/// - [`Error::InvalidComment`]: A comment contains `--` or ends with `-`.
+/// - [`Error::InvalidProcessingInstruction`]: A processing instruction contains `?>`.
 impl Document {
     pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         self.write_file_with_opts(path, WriteOptions::default())
     }
     pub fn write_file_with_opts<P: AsRef<Path>>(&self, path: P, opts: WriteOptions) -> Result<()> {
-        let mut file = File::create(path)?;
-        self.write_with_opts(&mut file, opts)
+        let file = File::create(path)?;
+        self.write_buffered(file, opts)
+    }
+
+    /// Same as [`Document::write_with_opts`], but the output is buffered, which avoids
+    /// many small writes to `writer` (e.g. when writing to a file or a socket).
+    /// The buffer is flushed before returning.
+    pub fn write_buffered<W: Write>(&self, writer: W, opts: WriteOptions) -> Result<()> {
+        let mut writer = BufWriter::new(writer);
+        self.write_with_opts(&mut writer, opts)?;
+        writer.flush()?;
+        Ok(())
     }
 
     pub fn write_str(&self) -> Result<String> {
@@ -254,74 +1772,269 @@ impl Document {
     }
     pub fn write_str_with_opts(&self, opts: WriteOptions) -> Result<String> {
         let mut buf: Vec<u8> = Vec::with_capacity(200);
-        self.write_with_opts(&mut buf, opts)?;
+        self.write_utf8(&mut buf, &opts)?;
         Ok(String::from_utf8(buf)?)
     }
 
+    /// Write the document without the elements deeper than `max_depth`,
+    /// see [`WriteOptions::max_depth`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a><b/></a><c/></root>
+    /// "#).unwrap();
+    /// let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root>
+    ///   <a>
+    ///     <!-- ... -->
+    ///   </a>
+    ///   <c/>
+    /// </root>"#;
+    /// assert_eq!(doc.write_str_max_depth(1).unwrap(), expected);
+    /// ```
+    pub fn write_str_max_depth(&self, max_depth: usize) -> Result<String> {
+        self.write_str_with_opts(WriteOptions {
+            max_depth: Some(max_depth),
+            ..WriteOptions::default()
+        })
+    }
+
+    /// Write the document in the canonical form of
+    /// [Canonical XML 1.0](https://www.w3.org/TR/xml-c14n) (without comments),
+    /// e.g. to compute a digest for a digital signature.
+    ///
+    /// The output has no XML declaration and no doctype, empty elements are written
+    /// as start and end tag, CDATA sections are written as escaped text, and all
+    /// comments and whitespace outside the root element are removed. Namespace
+    /// declarations come first, sorted by prefix, and declarations which repeat the
+    /// value of an ancestor are left out. Attributes are sorted by namespace and then
+    /// by local name, with attributes without a prefix first. Text is written as it is
+    /// stored, so parse the document with [`ReadOptions::preserve_formatting`] to keep
+    /// its whitespace.
+    ///
+    /// Default attribute values declared in a DTD are not added, so the output only
+    /// matches the specification for documents without an internal DTD subset.
+    ///
+    /// # Errors
+    /// - [`Error::UndeclaredPrefix`]: An attribute prefix is not declared.
+    /// - [`Error::InvalidProcessingInstruction`]: A processing instruction contains `?>`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <!-- comment -->
+    /// <root xmlns:b="http://b" xmlns:a="http://a" b:x="1" a:y="2" z="3">
+    ///     <item xmlns:a="http://a"/>
+    ///     <![CDATA[<&>]]>
+    /// </root>
+    /// "#).unwrap();
+    /// assert_eq!(
+    ///     doc.write_c14n().unwrap(),
+    ///     r#"<root xmlns:a="http://a" xmlns:b="http://b" z="3" a:y="2" b:x="1"><item></item>&lt;&amp;&gt;</root>"#
+    /// );
+    /// ```
+    pub fn write_c14n(&self) -> Result<String> {
+        let mut out = String::new();
+        c14n::write_root_nodes(self, self.root_nodes(), &mut out)?;
+        Ok(out)
+    }
+
     pub fn write(&self, writer: &mut impl Write) -> Result<()> {
         self.write_with_opts(writer, WriteOptions::default())
     }
+
+    /// Write the document into `writer`, encoded into the declared encoding.
+    ///
+    /// The output is written as it is produced and the whole document is never held
+    /// in memory, so this is suitable for large documents. This also means that `writer`
+    /// receives many small writes and is not flushed; use [`Document::write_buffered`]
+    /// for unbuffered writers such as files or sockets.
     pub fn write_with_opts(&self, writer: &mut impl Write, opts: WriteOptions) -> Result<()> {
+        let encoding = match self.write_encoding(&opts) {
+            Some(label) => Encoding::for_label(label.as_bytes()).ok_or(Error::CannotDecode)?,
+            None => UTF_8,
+        };
+        if encoding == UTF_8 {
+            if self.bom == Some(UTF_8) {
+                writer.write_all(&[0xef, 0xbb, 0xbf])?;
+            }
+            return self.write_utf8(writer, &opts);
+        }
+        let mut writer = if encoding == UTF_16LE || encoding == UTF_16BE {
+            // Plain "UTF-16" resolves to little endian, so keep big endian of the parsed BOM.
+            let little_endian = encoding == UTF_16LE && self.bom != Some(UTF_16BE);
+            let mut writer = EncodingWriter::utf16(writer, little_endian);
+            writer.write_all("\u{feff}".as_bytes())?;
+            writer
+        } else {
+            EncodingWriter::new(writer, encoding.new_encoder())
+        };
+        self.write_utf8(&mut writer, &opts)?;
+        writer.finish()
+    }
+
+    /// The encoding which should be declared in the XML declaration.
+    fn write_encoding<'a>(&'a self, opts: &'a WriteOptions) -> Option<&'a str> {
+        opts.encoding.as_deref().or(self.encoding.as_deref())
+    }
+
+    fn write_utf8(&self, writer: &mut impl Write, opts: &WriteOptions) -> Result<()> {
         let container = self.container();
-        let mut writer = Writer::new_with_indent(writer, opts.indent_char, opts.indent_size);
-        if opts.write_decl {
-            self.write_decl(&mut writer)?;
+        let mut writer = opts.xml_writer(writer);
+        if opts.write_decl.unwrap_or(self.declaration) {
+            self.write_decl(&mut writer, opts)?;
         }
-        self.write_nodes(&mut writer, container.children(self))?;
+        self.write_nodes(&mut writer, container.children(self), true, opts)?;
         writer.write_event(Event::Eof)?;
         Ok(())
     }
 
-    fn write_decl(&self, writer: &mut Writer<impl Write>) -> Result<()> {
+    fn write_decl(&self, writer: &mut Writer<impl Write>, opts: &WriteOptions) -> Result<()> {
         let standalone = match self.standalone {
-            true => Some("yes"),
-            false => None,
+            Some(true) => Some("yes"),
+            Some(false) => Some("no"),
+            None => None,
         };
         writer.write_event(Event::Decl(BytesDecl::new(
             self.version.as_str(),
-            Some("UTF-8"),
+            self.write_encoding(opts),
             standalone,
         )))?;
         Ok(())
     }
 
-    fn write_nodes(&self, writer: &mut Writer<impl Write>, nodes: &[Node]) -> Result<()> {
+    /// Write `nodes` as a self-contained fragment: every element also declares
+    /// the namespaces that its subtree uses but which are declared by its ancestors.
+    ///
+    /// [`WriteOptions::write_decl`] is ignored, since a fragment has no declaration.
+    pub(crate) fn write_fragment_str(&self, nodes: &[Node], opts: WriteOptions) -> Result<String> {
+        let mut buf: Vec<u8> = Vec::with_capacity(200);
+        let mut writer = opts.xml_writer(&mut buf);
+        for node in nodes {
+            if let Node::Element(element) = node {
+                let own_decls = element.namespace_decls(self);
+                let mut external = element.collect_external_namespace_decls(self)?;
+                external.retain(|prefix, url| {
+                    !(prefix.is_empty() && url.is_empty())
+                        && prefix != "xml"
+                        && prefix != "xmlns"
+                        && !own_decls.contains_key(prefix)
+                });
+                self.write_element_with_decls(&mut writer, *element, &external, true, &opts)?;
+            } else {
+                self.write_nodes(&mut writer, std::slice::from_ref(node), true, &opts)?;
+            }
+        }
+        Ok(String::from_utf8(buf)?)
+    }
+
+    /// Write `nodes`. If `indent` is false, the nodes are written without indentation
+    /// even if the writer is indenting.
+    fn write_nodes(
+        &self,
+        writer: &mut Writer<impl Write>,
+        nodes: &[Node],
+        indent: bool,
+        opts: &WriteOptions,
+    ) -> Result<()> {
         for node in nodes {
+            if !indent {
+                // Empty text prevents indentation of the next event.
+                writer.write_event(Event::Text(BytesText::new("")))?;
+            }
             match node {
-                Node::Element(eid) => self.write_element(writer, *eid)?,
-                Node::Text(text) => writer.write_event(Event::Text(BytesText::new(text)))?,
+                Node::Element(eid) => {
+                    self.write_element_with_decls(writer, *eid, &HashMap::new(), indent, opts)?
+                }
+                Node::Text(text) => {
+                    let text = opts.text_escaping.escape_text(text);
+                    writer.write_event(Event::Text(BytesText::from_escaped(text)))?
+                }
+                // The internal subset is markup, which must not be escaped.
+                Node::DocType(text) if opts.preserve_formatting => {
+                    writer.write_event(Event::DocType(BytesText::from_escaped(text)))?
+                }
                 Node::DocType(text) => writer.write_event(Event::DocType(BytesText::new(text)))?,
                 // Comment, CData, and PI content is not escaped.
                 Node::Comment(text) => {
+                    check_node_text(node)?;
                     writer.write_event(Event::Comment(BytesText::from_escaped(text)))?
                 }
-                Node::CData(text) => writer.write_event(Event::CData(BytesCData::new(text)))?,
-                Node::PI(text) => writer.write_event(Event::PI(BytesText::from_escaped(text)))?,
+                Node::CData(text) => {
+                    // `]]>` would end the section, so it is split across two sections.
+                    let text = text.replace("]]>", "]]]]><![CDATA[>");
+                    writer.write_event(Event::CData(BytesCData::new(text)))?
+                }
+                Node::PI(text) => {
+                    check_node_text(node)?;
+                    writer.write_event(Event::PI(BytesText::from_escaped(text)))?
+                }
+                Node::RawText(text) => {
+                    writer.write_event(Event::Text(BytesText::from_escaped(text)))?
+                }
             };
         }
         Ok(())
     }
 
-    fn write_element(&self, writer: &mut Writer<impl Write>, element: Element) -> Result<()> {
+    /// Write `element`, adding `extra_decls` to its own namespace declarations.
+    fn write_element_with_decls(
+        &self,
+        writer: &mut Writer<impl Write>,
+        element: Element,
+        extra_decls: &HashMap<String, String>,
+        indent: bool,
+        opts: &WriteOptions,
+    ) -> Result<()> {
         let name_str = element.full_name(self);
         let mut start = BytesStart::new(name_str);
-        // The copy in BTreeMap ensures that we have a deterministic iteration order.
-        let attributes = BTreeMap::from_iter(element.attributes(self).iter());
+        let escaping = opts.text_escaping;
+        let attributes = match opts.attribute_order {
+            _ if opts.preserve_formatting => element.attributes_in_source_order(self),
+            AttributeOrder::Sorted => element.attributes_sorted(self),
+            AttributeOrder::Source => element.attributes_in_source_order(self),
+        };
         for (key, val) in attributes {
-            start.push_attribute((key.as_str(), val.as_str()));
+            let val = escaping.escape_attribute(val);
+            start.push_attribute((key.as_bytes(), val.as_bytes()));
         }
-        let namespaces = BTreeMap::from_iter(element.namespace_decls(self).iter());
+        let mut namespaces = BTreeMap::from_iter(element.namespace_decls(self).iter());
+        namespaces.extend(extra_decls.iter());
         for (prefix, val) in namespaces {
             let attr_name = if prefix.is_empty() {
                 "xmlns".to_string()
             } else {
                 format!("xmlns:{}", prefix)
             };
-            start.push_attribute((attr_name.as_str(), val.as_str()));
+            let val = escaping.escape_attribute(val);
+            start.push_attribute((attr_name.as_bytes(), val.as_bytes()));
         }
-        if element.has_children(self) {
+        if opts.void_elements.contains(name_str) {
+            writer.write_event(Event::Empty(start))?;
+        } else if element.has_children(self) {
+            writer.write_event(Event::Start(start))?;
+            // Whitespace is significant, so the content is written without indentation.
+            let indent = indent && element.attribute(self, "xml:space") != Some("preserve");
+            if opts.max_depth.is_some_and(|max| element.depth(self) >= max) {
+                if !indent {
+                    writer.write_event(Event::Text(BytesText::new("")))?;
+                }
+                writer.write_event(Event::Comment(BytesText::from_escaped(" ... ")))?;
+            } else {
+                self.write_nodes(writer, element.children(self), indent, opts)?;
+            }
+            if !indent {
+                // Empty text prevents indentation of the end tag.
+                writer.write_event(Event::Text(BytesText::new("")))?;
+            }
+            writer.write_event(Event::End(BytesEnd::new(name_str)))?;
+        } else if element.is_force_expanded(self) {
             writer.write_event(Event::Start(start))?;
-            self.write_nodes(writer, element.children(self))?;
+            // Empty text prevents indentation of the end tag.
+            writer.write_event(Event::Text(BytesText::new("")))?;
             writer.write_event(Event::End(BytesEnd::new(name_str)))?;
         } else {
             writer.write_event(Event::Empty(start))?;
@@ -338,6 +2051,43 @@ impl FromStr for Document {
     }
 }
 
+impl TryFrom<&str> for Document {
+    type Error = Error;
+
+    fn try_from(s: &str) -> Result<Document> {
+        Document::parse_str(s)
+    }
+}
+
+impl TryFrom<String> for Document {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Document> {
+        Document::parse_str(&s)
+    }
+}
+
+/// Writes the document using [`WriteOptions::default()`], see [`Document::write_str`].
+///
+/// # Panics
+///
+/// Formatting panics (e.g. in `to_string()`) if the document cannot be written, i.e. when
+/// it contains a comment with `--`, or a processing instruction with `?>`.
+/// Use [`Document::write_str`] to handle these errors.
+///
+/// ```rust
+/// use biodivine_xml_doc::Document;
+///
+/// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?><root/>"#).unwrap();
+/// assert_eq!(doc.to_string(), "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root/>");
+/// ```
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let xml = self.write_str().map_err(|_| std::fmt::Error)?;
+        f.write_str(&xml)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -361,14 +2111,76 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_clone() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <root attr="value">
+            <a>Text</a>
+        </root>
+        "#;
+        let doc = Document::from_str(xml).unwrap();
+        let original = doc.write_str().unwrap();
+        let root = doc.root_element().unwrap();
+        let a = root.find(&doc, "a").unwrap();
+
+        let mut copy = doc.clone();
+        a.set_text_content(&mut copy, "Changed");
+        root.set_attribute(&mut copy, "attr", "changed").unwrap();
+        Element::build("b").push_to(&mut copy, root);
+        a.detatch(&mut copy).unwrap();
+
+        assert_eq!(doc.write_str().unwrap(), original);
+        assert_eq!(a.text_content(&doc), "Text");
+        assert_eq!(a.parent(&doc), Some(root));
+        assert_eq!(root.attribute(&doc, "attr"), Some("value"));
+        assert_eq!(root.child_elements(&doc), vec![a]);
+        assert_eq!(a.text_content(&copy), "Changed");
+        assert_eq!(a.parent(&copy), None);
+        assert_ne!(copy.write_str().unwrap(), original);
+    }
+
+    #[test]
+    fn test_compact() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+  <a x="1">
+    <b>text</b>
+    <c/>
+  </a>
+  <d>
+    <e/>
+  </d>
+</root>
+<!--end-->"#;
+        let mut doc = Document::parse_str(xml).unwrap();
+        let root = doc.root_element().unwrap();
+        let extra = Element::build("extra").push_to(&mut doc, root);
+        Element::build("inner").push_to(&mut doc, extra);
+        extra.detatch(&mut doc).unwrap();
+        Element::new(&mut doc, "unused");
+        assert_eq!(doc.element_count(), 9);
+
+        doc.compact();
+        assert_eq!(doc.element_count(), 6);
+        assert_eq!(doc.write_str().unwrap(), xml);
+        let root = doc.root_element().unwrap();
+        for elem in root.child_elements_recursive(&doc) {
+            let parent = elem.parent(&doc).unwrap();
+            assert!(parent.child_elements(&doc).contains(&elem));
+        }
+        assert_eq!(root.parent(&doc), Some(doc.container()));
+    }
+
     #[test]
     fn test_enforce_encoding() {
         // This document can be parsed without issues if we don't require a specific encoding,
         // but it is not UTF-8 and hence should fail if we specifically request UTF-8.
         let xml = "<?xml version=\"1.0\" encoding=\"US-ASCII\"?><test></test>";
         assert!(Document::parse_str(xml).is_ok());
-        let mut opts = ReadOptions::default();
-        opts.enforce_encoding = true;
+        let mut opts = ReadOptions {
+            enforce_encoding: true,
+            ..ReadOptions::default()
+        };
         // We have not specified any encoding, hence this should always fail.
         assert!(matches!(
             Document::parse_str_with_opts(xml, opts.clone()),
@@ -389,8 +2201,10 @@ mod tests {
         // library logic.
         let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?><test></test>";
         assert!(Document::parse_str(xml).is_ok());
-        let mut opts = ReadOptions::default();
-        opts.enforce_encoding = true;
+        let mut opts = ReadOptions {
+            enforce_encoding: true,
+            ..ReadOptions::default()
+        };
         assert!(matches!(
             Document::parse_str_with_opts(xml, opts.clone()),
             Err(Error::CannotDecode)
@@ -404,4 +2218,30 @@ mod tests {
         let doc = Document::parse_str_with_opts(xml, opts.clone()).unwrap();
         assert_eq!(doc.root_element().unwrap().name(&doc), "test");
     }
+
+    #[test]
+    fn test_validate_well_formed() {
+        let mut doc = Document::new();
+        let errors = doc.validate_well_formed().unwrap_err();
+        assert!(matches!(errors[..], [Error::NoRootElement]));
+
+        let root = Element::new(&mut doc, "1root");
+        doc.push_root_node(root).unwrap();
+        root.set_namespace_decl(&mut doc, "a:b", "http://a");
+        root.mut_attributes(&mut doc)
+            .insert("x y".to_string(), "1".to_string());
+        let other = Element::new(&mut doc, "other");
+        doc.push_root_node(other).unwrap();
+        let errors = doc.validate_well_formed().unwrap_err();
+        let names: Vec<&str> = errors
+            .iter()
+            .filter_map(|error| match error {
+                Error::InvalidName(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(matches!(errors[0], Error::MultipleRootElements));
+        assert_eq!(names, vec!["1root", "a:b", "x y"]);
+        assert_eq!(errors.len(), 4);
+    }
 }
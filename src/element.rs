@@ -1,14 +1,66 @@
-use crate::document::{Document, Node};
+use crate::document::{Document, MemoryReport, Node, WriteOptions};
 use crate::error::{Error, Result};
+use crate::parser::{is_valid_xml_name, ReadOptions};
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-#[derive(Debug)]
+// Cloning copies the element ids of `children`, which is what cloning the whole store needs.
+#[derive(Debug, Clone)]
 pub(crate) struct ElementData {
-    full_name: String,
+    // Names are shared, so that documents with many elements of the same name
+    // (as interned by the parser) only store each name once.
+    full_name: Arc<str>,
     attributes: HashMap<String, String>, // q:attr="val" => {"q:attr": "val"}
+    // Full names of attributes in source (or insertion) order. May contain names which were
+    // since removed through `mut_attributes`, or miss names inserted through it.
+    attribute_order: Vec<Arc<str>>,
     namespace_decls: HashMap<String, String>, // local namespace newly defined in attributes
     parent: Option<Element>,
     children: Vec<Node>,
+    // Write as `<tag></tag>` instead of `<tag/>` when there are no children.
+    force_expanded: bool,
+}
+
+impl ElementData {
+    /// Add the contents of this element to `report`. `names` are the shared names
+    /// which are already counted.
+    pub(crate) fn add_to_report(&self, report: &mut MemoryReport, names: &mut HashSet<*const u8>) {
+        use std::mem::size_of;
+
+        report.attributes += self.attributes.len();
+        report.namespace_decls += self.namespace_decls.len();
+        let mut heap = 0;
+        for name in std::iter::once(&self.full_name).chain(self.attribute_order.iter()) {
+            if names.insert(name.as_ptr()) {
+                heap += name.len();
+            }
+        }
+        for map in [&self.attributes, &self.namespace_decls] {
+            // Approximately one control byte per bucket.
+            heap += map.capacity() * (size_of::<(String, String)>() + 1);
+            heap += map
+                .iter()
+                .map(|(key, value)| key.capacity() + value.capacity())
+                .sum::<usize>();
+        }
+        heap += self.attribute_order.capacity() * size_of::<Arc<str>>();
+        heap += self.children.capacity() * size_of::<Node>();
+        for node in &self.children {
+            match node {
+                Node::Element(_) => (),
+                Node::Text(text)
+                | Node::Comment(text)
+                | Node::CData(text)
+                | Node::PI(text)
+                | Node::DocType(text)
+                | Node::RawText(text) => {
+                    report.text_bytes += text.len();
+                    heap += text.capacity();
+                }
+            }
+        }
+        report.heap_bytes += heap;
+    }
 }
 
 /// An easy way to build a new element
@@ -35,9 +87,20 @@ pub(crate) struct ElementData {
 ///     .text_content("No Name")
 ///     .push_to(&mut doc, root);
 ///
+/// let description = Element::build("description")
+///     .add_text("Written in ")
+///     .add_child_element(Element::build("b").text_content("bold"))
+///     .add_comment("TODO")
+///     .push_to(&mut doc, root);
+/// let children = description.children(&doc);
+/// assert_eq!(children[0].as_text(), Some("Written in "));
+/// assert_eq!(children[1].text_content(&doc), "bold");
+/// assert_eq!(children[2].as_comment(), Some("TODO"));
+///
 /// /* Equivalent xml:
 ///   <root id="main" class="main">
 ///     <name>No Name</name>
+///     <description>Written in <b>bold</b><!--TODO--></description>
 ///   </root>
 /// */
 /// ```
@@ -46,8 +109,18 @@ pub(crate) struct ElementData {
 pub struct ElementBuilder {
     full_name: String,
     attributes: HashMap<String, String>,
+    attribute_order: Vec<String>,
     namespace_decls: HashMap<String, String>,
-    text_content: Option<String>,
+    children: Vec<PendingChild>,
+}
+
+/// A child node which is created when the [`ElementBuilder`] is finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PendingChild {
+    Text(String),
+    Comment(String),
+    CData(String),
+    Element(ElementBuilder),
 }
 
 impl ElementBuilder {
@@ -55,8 +128,9 @@ impl ElementBuilder {
         ElementBuilder {
             full_name,
             attributes: HashMap::new(),
+            attribute_order: Vec::new(),
             namespace_decls: HashMap::new(),
-            text_content: None,
+            children: Vec::new(),
         }
     }
 
@@ -76,7 +150,11 @@ impl ElementBuilder {
         S: Into<String>,
         T: Into<String>,
     {
-        self.attributes.insert(name.into(), value.into());
+        let name = name.into();
+        if !self.attributes.contains_key(&name) {
+            self.attribute_order.push(name.clone());
+        }
+        self.attributes.insert(name, value.into());
         self
     }
 
@@ -89,15 +167,86 @@ impl ElementBuilder {
         self
     }
 
+    /// Declare the default namespace (`xmlns="namespace"`).
+    /// Same as `.namespace_decl("", namespace)`.
+    pub fn default_namespace<S: Into<String>>(self, namespace: S) -> Self {
+        self.namespace_decl("", namespace)
+    }
+
+    /// Add several namespace declarations at once, as `(prefix, namespace)` pairs.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let decls = HashMap::from([("a", "http://a"), ("b", "http://b")]);
+    /// let mut doc = Document::new();
+    /// let root = Element::build("root")
+    ///     .default_namespace("http://default")
+    ///     .namespace_decls(decls)
+    ///     .finish(&mut doc);
+    /// assert_eq!(root.namespace(&doc), Some("http://default"));
+    /// assert_eq!(root.namespace_for_prefix(&doc, "b"), Some("http://b"));
+    /// ```
+    pub fn namespace_decls<I, S, T>(mut self, decls: I) -> Self
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: Into<String>,
+        T: Into<String>,
+    {
+        for (prefix, namespace) in decls {
+            self.namespace_decls.insert(prefix.into(), namespace.into());
+        }
+        self
+    }
+
+    /// Replaces all previously added children with a single [`Node::Text`].
     pub fn text_content<S: Into<String>>(mut self, text: S) -> Self {
-        self.text_content = Some(text.into());
+        self.children = vec![PendingChild::Text(text.into())];
+        self
+    }
+
+    /// Append a [`Node::Text`] after the previously added children.
+    pub fn add_text<S: Into<String>>(mut self, text: S) -> Self {
+        self.children.push(PendingChild::Text(text.into()));
+        self
+    }
+
+    /// Append a [`Node::Comment`] after the previously added children.
+    pub fn add_comment<S: Into<String>>(mut self, comment: S) -> Self {
+        self.children.push(PendingChild::Comment(comment.into()));
+        self
+    }
+
+    /// Append a [`Node::CData`] after the previously added children.
+    pub fn add_cdata<S: Into<String>>(mut self, cdata: S) -> Self {
+        self.children.push(PendingChild::CData(cdata.into()));
+        self
+    }
+
+    /// Append a child element after the previously added children.
+    /// The child is built when this element is finished.
+    pub fn add_child_element(mut self, child: ElementBuilder) -> Self {
+        self.children.push(PendingChild::Element(child));
         self
     }
 
     pub fn finish(self, doc: &mut Document) -> Element {
-        let elem = Element::with_data(doc, self.full_name, self.attributes, self.namespace_decls);
-        if let Some(text) = self.text_content {
-            elem.push_child(doc, Node::Text(text)).unwrap();
+        let elem = Element::with_data(
+            doc,
+            self.full_name.into(),
+            self.attributes,
+            self.attribute_order.into_iter().map(Arc::from).collect(),
+            self.namespace_decls,
+        );
+        for child in self.children {
+            let node = match child {
+                PendingChild::Text(text) => Node::Text(text),
+                PendingChild::Comment(text) => Node::Comment(text),
+                PendingChild::CData(text) => Node::CData(text),
+                PendingChild::Element(builder) => Node::Element(builder.finish(doc)),
+            };
+            elem.push_child(doc, node).unwrap();
         }
         elem
     }
@@ -155,7 +304,47 @@ impl Element {
     /// If full_name contains `:`,
     /// everything before that will be interpreted as a namespace prefix.
     pub fn new<S: Into<String>>(doc: &mut Document, full_name: S) -> Self {
-        Self::with_data(doc, full_name.into(), HashMap::new(), HashMap::new())
+        Self::with_data(
+            doc,
+            Arc::from(full_name.into()),
+            HashMap::new(),
+            Vec::new(),
+            HashMap::new(),
+        )
+    }
+
+    /// Create a new element with `full_name` and the given attributes.
+    ///
+    /// The attributes are recorded in the order of iteration (see [`crate::AttributeOrder`]).
+    /// If an attribute name repeats, the last value is used.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    /// use std::collections::HashMap;
+    ///
+    /// let mut doc = Document::new();
+    /// let attrs = HashMap::from([("id".to_string(), "main".to_string())]);
+    /// let elem = Element::with_attributes(&mut doc, "root", attrs);
+    /// assert_eq!(elem.attribute(&doc, "id"), Some("main"));
+    ///
+    /// let elem = Element::with_attributes(&mut doc, "item", [("x", "1"), ("y", "2")]);
+    /// assert_eq!(elem.outer_xml(&doc).unwrap(), r#"<item x="1" y="2"/>"#);
+    /// ```
+    pub fn with_attributes<S, I, K, V>(doc: &mut Document, full_name: S, attributes: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut map = HashMap::new();
+        let mut order = Vec::new();
+        for (name, value) in attributes {
+            let name: String = name.into();
+            order.push(Arc::from(name.as_str()));
+            map.insert(name, value.into());
+        }
+        Self::with_data(doc, Arc::from(full_name.into()), map, order, HashMap::new())
     }
 
     /// Chain methods to build an element easily.
@@ -180,31 +369,98 @@ impl Element {
 
     pub(crate) fn with_data(
         doc: &mut Document,
-        full_name: String,
+        full_name: Arc<str>,
         attributes: HashMap<String, String>,
+        attribute_order: Vec<Arc<str>>,
         namespace_decls: HashMap<String, String>,
     ) -> Element {
         let elem = Element { id: doc.counter };
         let elem_data = ElementData {
             full_name,
             attributes,
+            attribute_order,
             namespace_decls,
             parent: None,
             children: vec![],
+            force_expanded: false,
         };
         doc.store.push(elem_data);
         doc.counter += 1;
         elem
     }
 
+    /// Copy this element and its descendants from `src` into `dst`.
+    ///
+    /// The copy is created in the store of `dst` and has no parent.
+    pub(crate) fn deep_copy(&self, src: &Document, dst: &mut Document) -> Element {
+        let data = self.data(src);
+        let copy = Element::with_data(
+            dst,
+            data.full_name.clone(),
+            data.attributes.clone(),
+            data.attribute_order.clone(),
+            data.namespace_decls.clone(),
+        );
+        copy.mut_data(dst).force_expanded = data.force_expanded;
+        for child in &data.children {
+            let child = child.deep_copy(src, dst);
+            copy.push_child(dst, child).unwrap();
+        }
+        copy
+    }
+
+    /// Compare elements by their position in the store, i.e. in the order of creation.
+    pub(crate) fn cmp_store_position(&self, other: &Element) -> std::cmp::Ordering {
+        self.id.cmp(&other.id)
+    }
+
+    /// Iterate over all elements in the store of `doc`, excluding the container.
+    pub(crate) fn stored_elements(doc: &Document) -> impl Iterator<Item = Element> {
+        (1..doc.store.len()).map(|id| Element { id })
+    }
+
+    /// Replace the store of `doc` with only the data of `elements`, in the given order.
+    ///
+    /// The first element must be the container, and `elements` must contain the parent
+    /// and all children of each element. References between elements are updated
+    /// to the new ids, which are the positions in `elements`.
+    pub(crate) fn rebuild_store(doc: &mut Document, elements: &[Element]) {
+        let mut new_ids = vec![usize::MAX; doc.store.len()];
+        for (new_id, elem) in elements.iter().enumerate() {
+            new_ids[elem.id] = new_id;
+        }
+        let remap = |elem: Element| Element {
+            id: new_ids[elem.id],
+        };
+        let mut old_store: Vec<Option<ElementData>> = std::mem::take(&mut doc.store)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut store = Vec::with_capacity(elements.len());
+        for elem in elements {
+            let mut data = old_store[elem.id].take().unwrap();
+            data.parent = data.parent.map(remap);
+            for child in &mut data.children {
+                if let Node::Element(child) = child {
+                    *child = remap(*child);
+                }
+            }
+            store.push(data);
+        }
+        doc.store = store;
+        doc.counter = doc.store.len();
+    }
+
     /// Create a container Element
     pub(crate) fn container() -> (Element, ElementData) {
         let elem_data = ElementData {
-            full_name: String::new(),
+            full_name: Arc::from(""),
             attributes: HashMap::new(),
+            attribute_order: Vec::new(),
             namespace_decls: HashMap::new(),
             parent: None,
             children: Vec::new(),
+            force_expanded: false,
         };
         let elem = Element { id: 0 };
         (elem, elem_data)
@@ -247,7 +503,7 @@ impl Element {
     ///
     /// Note that this crate allows Document to have multiple elements, even though it's not valid xml.
     pub fn is_root(&self, doc: &Document) -> bool {
-        self.parent(doc).map_or(false, |p| p.is_container())
+        self.parent(doc).is_some_and(|p| p.is_container())
     }
 
     /// Returns the "top" parent of this element. If the element is attached, the "top" parent
@@ -263,14 +519,177 @@ impl Element {
         e
     }
 
+    /// Number of ancestors of this element, not counting the container.
+    ///
+    /// The root element (or the top of a detached sub-tree) has depth `0`,
+    /// its children have depth `1`, and so on.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a><b/></a></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let a = root.find(&doc, "a").unwrap();
+    /// let b = a.find(&doc, "b").unwrap();
+    /// assert_eq!(root.depth(&doc), 0);
+    /// assert_eq!(a.depth(&doc), 1);
+    /// assert_eq!(b.depth(&doc), 2);
+    /// ```
+    pub fn depth(&self, doc: &Document) -> usize {
+        let mut depth = 0;
+        let mut e = *self;
+        while let Some(parent) = e.parent(doc) {
+            if parent.is_container() {
+                break;
+            }
+            depth += 1;
+            e = parent;
+        }
+        depth
+    }
+
+    /// Find the first element satisfying `predicate`, starting with this element and
+    /// continuing with its ancestors. The container is never returned.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><section role="main"><section><p/></section></section></root>
+    /// "#).unwrap();
+    /// let p = doc.find("p").unwrap();
+    /// let main = p.closest(&doc, |e, doc| e.attribute(doc, "role").is_some()).unwrap();
+    /// assert_eq!(main.attribute(&doc, "role"), Some("main"));
+    /// assert_eq!(p.closest(&doc, |e, doc| e.name(doc) == "p"), Some(p));
+    /// assert_eq!(p.closest(&doc, |e, doc| e.name(doc) == "table"), None);
+    /// ```
+    pub fn closest<F>(&self, doc: &Document, predicate: F) -> Option<Element>
+    where
+        F: Fn(&Element, &Document) -> bool,
+    {
+        let mut current = Some(*self);
+        while let Some(e) = current {
+            if e.is_container() {
+                break;
+            }
+            if predicate(&e, doc) {
+                return Some(e);
+            }
+            current = e.parent(doc);
+        }
+        None
+    }
+
+    /// Find the first element with name `name`, starting with this element and
+    /// continuing with its ancestors. Same as `closest` with a predicate comparing names.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><section id="1"><p/></section></root>
+    /// "#).unwrap();
+    /// let p = doc.find("p").unwrap();
+    /// let section = p.closest_by_name(&doc, "section").unwrap();
+    /// assert_eq!(section.attribute(&doc, "id"), Some("1"));
+    /// ```
+    pub fn closest_by_name(&self, doc: &Document, name: &str) -> Option<Element> {
+        self.closest(doc, |e, doc| e.name(doc) == name)
+    }
+
+    /// A human-readable location of this element, e.g. `/package/metadata/author[2]`,
+    /// useful for error messages.
+    ///
+    /// The path consists of the full names (including prefixes) of this element and its
+    /// ancestors, starting with the root element (or the top of a detached sub-tree).
+    /// If an element has siblings with the same full name, its 1-based position among them
+    /// is added in brackets. The path of the container is `/`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <package xmlns:dc="http://purl.org/dc/elements/1.1/">
+    ///     <metadata><dc:title/><author/><author/></metadata>
+    /// </package>
+    /// "#).unwrap();
+    /// let metadata = doc.find("metadata").unwrap();
+    /// let children = metadata.child_elements(&doc);
+    /// assert_eq!(metadata.path(&doc), "/package/metadata");
+    /// assert_eq!(children[0].path(&doc), "/package/metadata/dc:title");
+    /// assert_eq!(children[2].path(&doc), "/package/metadata/author[2]");
+    /// ```
+    pub fn path(&self, doc: &Document) -> String {
+        let mut steps = Vec::new();
+        let mut e = *self;
+        while let Some(parent) = e.parent(doc) {
+            let name = e.full_name(doc);
+            let mut count = 0;
+            let mut position = 0;
+            for sibling in parent.child_elements(doc) {
+                if sibling.full_name(doc) == name {
+                    count += 1;
+                    if sibling == e {
+                        position = count;
+                    }
+                }
+            }
+            if count > 1 {
+                steps.push(format!("{}[{}]", name, position));
+            } else {
+                steps.push(name.to_string());
+            }
+            if parent.is_container() {
+                break;
+            }
+            e = parent;
+        }
+        if !e.has_parent(doc) && !e.is_container() {
+            // The top of a detached sub-tree.
+            steps.push(e.full_name(doc).to_string());
+        }
+        if steps.is_empty() {
+            return "/".to_string();
+        }
+        steps.reverse();
+        format!("/{}", steps.join("/"))
+    }
+
     /// Get full name of element, including its namespace prefix.
     /// Use [`Element::name()`] to get its name without the prefix.
     pub fn full_name<'a>(&self, doc: &'a Document) -> &'a str {
         &self.data(doc).full_name
     }
 
-    pub fn set_full_name<S: Into<String>>(&self, doc: &mut Document, name: S) {
+    /// Set the full name of element, including its prefix.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidName`]: `name` is not a valid XML name, or it is not of the form
+    ///   `name` or `prefix:name` (e.g. it has more than one `:`, or an empty prefix or name).
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element, Error};
+    ///
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "item");
+    /// elem.set_full_name(&mut doc, "ns:entry").unwrap();
+    /// assert_eq!(elem.prefix_name(&doc), ("ns", "entry"));
+    ///
+    /// for name in ["a:b:c", ":b", "a:", "1st"] {
+    ///     let result = elem.set_full_name(&mut doc, name);
+    ///     assert!(matches!(result, Err(Error::InvalidName(_))));
+    /// }
+    /// assert_eq!(elem.full_name(&doc), "ns:entry");
+    /// ```
+    pub fn set_full_name<S: Into<String>>(&self, doc: &mut Document, name: S) -> Result<()> {
+        let name = name.into();
+        if !is_valid_qualified_name(&name) {
+            return Err(Error::InvalidName(name));
+        }
         self.mut_data(doc).full_name = name.into();
+        Ok(())
     }
 
     /// Get prefix and name of element. If it doesn't have prefix, will return an empty string.
@@ -293,15 +712,22 @@ impl Element {
     /// or everything after `:` will be interpreted as part of element name.    
     ///
     /// If prefix is an empty string, removes prefix.
-    pub fn set_prefix<S: Into<String>>(&self, doc: &mut Document, prefix: S) {
+    ///
+    /// # Errors
+    /// - [`Error::InvalidName`]: `prefix` is not a valid XML name, or contains a `:`.
+    pub fn set_prefix<S: Into<String>>(&self, doc: &mut Document, prefix: S) -> Result<()> {
+        let prefix: String = prefix.into();
+        if !prefix.is_empty() && !is_valid_name_part(&prefix) {
+            return Err(Error::InvalidName(prefix));
+        }
         let data = self.mut_data(doc);
         let (_, name) = Self::separate_prefix_name(&data.full_name);
-        let prefix: String = prefix.into();
         if prefix.is_empty() {
-            data.full_name = name.to_string();
+            data.full_name = name.into();
         } else {
-            data.full_name = format!("{}:{}", prefix, name);
+            data.full_name = format!("{}:{}", prefix, name).into();
         }
+        Ok(())
     }
 
     /// Get name of element, without its namespace prefix.
@@ -314,16 +740,34 @@ impl Element {
 
     /// Set name of element, preserving its prefix.
     ///
-    /// `name` should not have a `:`,
-    /// or everything before `:` may be interpreted as namespace prefix.
-    pub fn set_name<S: Into<String>>(&self, doc: &mut Document, name: S) {
+    /// # Errors
+    /// - [`Error::InvalidName`]: `name` is not a valid XML name, or contains a `:`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element, Error};
+    ///
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "ns:item");
+    /// elem.set_name(&mut doc, "entry").unwrap();
+    /// assert_eq!(elem.full_name(&doc), "ns:entry");
+    ///
+    /// let result = elem.set_name(&mut doc, "1 2");
+    /// assert!(matches!(result, Err(Error::InvalidName(_))));
+    /// assert_eq!(elem.full_name(&doc), "ns:entry");
+    /// ```
+    pub fn set_name<S: Into<String>>(&self, doc: &mut Document, name: S) -> Result<()> {
+        let name: String = name.into();
+        if !is_valid_name_part(&name) {
+            return Err(Error::InvalidName(name));
+        }
         let data = self.mut_data(doc);
         let (prefix, _) = Self::separate_prefix_name(&data.full_name);
         if prefix.is_empty() {
             data.full_name = name.into();
         } else {
-            data.full_name = format!("{}:{}", prefix, name.into());
+            data.full_name = format!("{}:{}", prefix, name).into();
         }
+        Ok(())
     }
 
     /// Get attributes of element.
@@ -348,58 +792,368 @@ impl Element {
         &self.data(doc).attributes
     }
 
-    /// Get attribute value of an element by its full name. (Namespace prefix isn't stripped)
-    pub fn attribute<'a>(&self, doc: &'a Document, name: &str) -> Option<&'a str> {
-        self.attributes(doc).get(name).map(|v| v.as_str())
+    /// Get attributes of an element as `(full_name, value)` pairs, sorted by full name.
+    ///
+    /// This is the order in which attributes are written, and unlike iterating
+    /// [`Element::attributes`], it is deterministic.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <item name="a" id="1" b:attr="x" xmlns:b="http://b"/>
+    /// "#).unwrap();
+    /// let item = doc.root_element().unwrap();
+    /// assert_eq!(
+    ///     item.attributes_sorted(&doc),
+    ///     vec![("b:attr", "x"), ("id", "1"), ("name", "a")]
+    /// );
+    /// ```
+    pub fn attributes_sorted<'a>(&self, doc: &'a Document) -> Vec<(&'a str, &'a str)> {
+        let mut attributes: Vec<(&str, &str)> = self
+            .attributes(doc)
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        attributes.sort_unstable();
+        attributes
     }
 
-    /// Add or set attribute.
+    /// Get the full names of the attributes of this element, sorted.
+    /// Namespace declarations are not included.
     ///
-    /// If `name` contains a `:`,
-    /// everything before `:` will be interpreted as namespace prefix.
-    pub fn set_attribute<S, T>(&self, doc: &mut Document, name: S, value: T)
-    where
-        S: Into<String>,
-        T: Into<String>,
-    {
-        self.mut_attributes(doc).insert(name.into(), value.into());
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <item xmlns:b="http://b" name="a" id="1" b:attr="x"/>
+    /// "#).unwrap();
+    /// let item = doc.root_element().unwrap();
+    /// assert_eq!(item.attribute_names(&doc), vec!["b:attr", "id", "name"]);
+    /// ```
+    pub fn attribute_names<'a>(&self, doc: &'a Document) -> Vec<&'a str> {
+        let mut names: Vec<&str> = self
+            .attributes(doc)
+            .keys()
+            .map(|name| name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Get attributes as `(full_name, value)` pairs in the order in which they appear in the
+    /// parsed document, or in which they were added. Attributes which were added
+    /// through [`Element::mut_attributes`] come last, sorted by full name.
+    pub(crate) fn attributes_in_source_order<'a>(
+        &self,
+        doc: &'a Document,
+    ) -> Vec<(&'a str, &'a str)> {
+        let data = self.data(doc);
+        let mut seen = HashSet::new();
+        let mut attributes: Vec<(&str, &str)> = data
+            .attribute_order
+            .iter()
+            .filter_map(|name| {
+                let (name, value) = data.attributes.get_key_value(&**name)?;
+                seen.insert(name.as_str())
+                    .then_some((name.as_str(), value.as_str()))
+            })
+            .collect();
+        let mut rest: Vec<(&str, &str)> = data
+            .attributes
+            .iter()
+            .filter(|(name, _)| !seen.contains(name.as_str()))
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        rest.sort_unstable();
+        attributes.append(&mut rest);
+        attributes
     }
 
-    pub fn mut_attributes<'a>(&self, doc: &'a mut Document) -> &'a mut HashMap<String, String> {
-        &mut self.mut_data(doc).attributes
+    /// Get attribute value of an element by its full name. (Namespace prefix isn't stripped)
+    pub fn attribute<'a>(&self, doc: &'a Document, name: &str) -> Option<&'a str> {
+        self.attributes(doc).get(name).map(|v| v.as_str())
     }
 
-    /// Gets the namespace of this element.
+    /// Get attribute value of an element by its local name and namespace url.
     ///
-    /// Shorthand for `self.namespace_for_prefix(doc, self.prefix(doc))`.
-    pub fn namespace<'a>(&self, doc: &'a Document) -> Option<&'a str> {
-        self.namespace_for_prefix(doc, self.prefix(doc))
+    /// The prefix of each attribute is resolved using [`Element::namespace_for_prefix`].
+    /// Note that unprefixed attributes are in *no namespace* (even if a default namespace is
+    /// declared), so they are only matched when `namespace_url` is empty.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r##"<?xml version="1.0" encoding="UTF-8"?>
+    /// <svg xmlns="http://www.w3.org/2000/svg" xmlns:x="http://www.w3.org/1999/xlink">
+    ///     <use x:href="#a" href="#b" />
+    /// </svg>
+    /// "##).unwrap();
+    ///
+    /// let elem = doc.root_element().unwrap().child_elements(&doc)[0];
+    /// let xlink = "http://www.w3.org/1999/xlink";
+    /// assert_eq!(elem.attribute_ns(&doc, xlink, "href"), Some("#a"));
+    /// assert_eq!(elem.attribute_ns(&doc, "", "href"), Some("#b"));
+    /// assert_eq!(elem.attribute_ns(&doc, "http://www.w3.org/2000/svg", "href"), None);
+    /// ```
+    pub fn attribute_ns<'a>(
+        &self,
+        doc: &'a Document,
+        namespace_url: &str,
+        local_name: &str,
+    ) -> Option<&'a str> {
+        self.attributes(doc).iter().find_map(|(full_name, value)| {
+            let (prefix, name) = Self::separate_prefix_name(full_name);
+            if name != local_name {
+                return None;
+            }
+            let matches = if prefix.is_empty() {
+                namespace_url.is_empty()
+            } else {
+                self.namespace_for_prefix(doc, prefix) == Some(namespace_url)
+            };
+            if matches {
+                Some(value.as_str())
+            } else {
+                None
+            }
+        })
     }
 
-    /// Gets HashMap of `xmlns:prefix=namespace` declared in this element's attributes.
-    ///
-    /// Default namespace has empty string as key.
-    pub fn namespace_decls<'a>(&self, doc: &'a Document) -> &'a HashMap<String, String> {
-        &self.data(doc).namespace_decls
+    /// Returns `true` if the element has the attribute `local_name` in the namespace
+    /// `namespace_url`, see [`Element::attribute_ns`].
+    pub fn has_attribute_ns(&self, doc: &Document, namespace_url: &str, local_name: &str) -> bool {
+        self.attribute_ns(doc, namespace_url, local_name).is_some()
     }
 
-    pub fn mut_namespace_decls<'a>(
-        &self,
-        doc: &'a mut Document,
-    ) -> &'a mut HashMap<String, String> {
-        &mut self.mut_data(doc).namespace_decls
+    /// Get all attributes as `(namespace_url, local_name, value)`, sorted by their full name.
+    ///
+    /// Prefixes are resolved using [`Element::namespace_for_prefix`]. Unprefixed attributes
+    /// are in no namespace, so their namespace is `None`. Attributes with a prefix which
+    /// is not declared also have no namespace, and their local name is their full name.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r##"<?xml version="1.0" encoding="UTF-8"?>
+    /// <use xmlns="http://www.w3.org/2000/svg" xmlns:x="http://www.w3.org/1999/xlink"
+    ///     x:href="#a" href="#b" />
+    /// "##).unwrap();
+    ///
+    /// let elem = doc.root_element().unwrap();
+    /// let xlink = "http://www.w3.org/1999/xlink";
+    /// assert_eq!(elem.attributes_ns(&doc), vec![
+    ///     (None, "href".to_string(), "#b"),
+    ///     (Some(xlink.to_string()), "href".to_string(), "#a"),
+    /// ]);
+    /// assert!(elem.has_attribute_ns(&doc, xlink, "href"));
+    /// ```
+    pub fn attributes_ns<'a>(&self, doc: &'a Document) -> Vec<(Option<String>, String, &'a str)> {
+        self.attributes_sorted(doc)
+            .into_iter()
+            .map(|(full_name, value)| {
+                let (prefix, name) = Self::separate_prefix_name(full_name);
+                if prefix.is_empty() {
+                    return (None, name.to_string(), value);
+                }
+                match self.namespace_for_prefix(doc, prefix) {
+                    Some(namespace) => (Some(namespace.to_string()), name.to_string(), value),
+                    None => (None, full_name.to_string(), value),
+                }
+            })
+            .collect()
     }
 
-    pub fn set_namespace_decl<S, T>(&self, doc: &mut Document, prefix: S, namespace: T)
+    /// Add or set attribute.
+    ///
+    /// If `name` contains a `:`,
+    /// everything before `:` will be interpreted as namespace prefix.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidName`]: `name` is not a valid XML name.
+    pub fn set_attribute<S, T>(&self, doc: &mut Document, name: S, value: T) -> Result<()>
     where
         S: Into<String>,
         T: Into<String>,
     {
-        self.mut_namespace_decls(doc)
-            .insert(prefix.into(), namespace.into());
+        let name = name.into();
+        if !is_valid_xml_name(&name) {
+            return Err(Error::InvalidName(name));
+        }
+        let data = self.mut_data(doc);
+        if !data.attributes.contains_key(&name) {
+            data.attribute_order.retain(|n| **n != *name);
+            data.attribute_order.push(name.as_str().into());
+        }
+        data.attributes.insert(name, value.into());
+        Ok(())
     }
 
-    /// Get namespace value given prefix, for this element.
+    /// Add or set attribute `local_name` belonging to the namespace identified by
+    /// `namespace_url`, using the *closest* non-empty prefix declared for this namespace.
+    ///
+    /// An empty prefix cannot be used, because unprefixed attributes are in no namespace.
+    /// If `namespace_url` is empty, the attribute is set without a prefix.
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::UndeclaredNamespace`]: No prefix for `namespace_url` is in scope. The
+    ///   method never declares new namespaces; use [`Element::set_namespace_decl`] first.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Error};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root xmlns:x="http://www.w3.org/1999/xlink">
+    ///     <use />
+    /// </root>
+    /// "#).unwrap();
+    ///
+    /// let elem = doc.root_element().unwrap().child_elements(&doc)[0];
+    /// elem.set_attribute_ns(&mut doc, "http://www.w3.org/1999/xlink", "href", "#a").unwrap();
+    /// assert_eq!(elem.attribute(&doc, "x:href"), Some("#a"));
+    ///
+    /// let result = elem.set_attribute_ns(&mut doc, "http://unknown", "href", "#a");
+    /// assert!(matches!(result, Err(Error::UndeclaredNamespace(_))));
+    /// ```
+    pub fn set_attribute_ns<S: Into<String>>(
+        &self,
+        doc: &mut Document,
+        namespace_url: &str,
+        local_name: &str,
+        value: S,
+    ) -> Result<()> {
+        if namespace_url.is_empty() {
+            return self.set_attribute(doc, local_name, value);
+        }
+        let Some(prefix) = self.closest_attribute_prefix(doc, namespace_url) else {
+            return Err(Error::UndeclaredNamespace(namespace_url.to_string()));
+        };
+        let name = format!("{}:{}", prefix, local_name);
+        self.set_attribute(doc, name, value)
+    }
+
+    /// Same as [`Element::closest_prefix`], but ignores the default namespace and checks
+    /// that the returned prefix is not shadowed by a different url in this element.
+    fn closest_attribute_prefix<'a>(
+        &self,
+        doc: &'a Document,
+        namespace_url: &str,
+    ) -> Option<&'a str> {
+        let mut search = Some(*self);
+        while let Some(e) = search {
+            let mut candidates: Vec<&str> = e
+                .namespace_decls(doc)
+                .iter()
+                .filter(|(prefix, url)| !prefix.is_empty() && url.as_str() == namespace_url)
+                .map(|(prefix, _)| prefix.as_str())
+                .collect();
+            candidates.sort();
+            let valid = candidates
+                .into_iter()
+                .find(|prefix| self.namespace_for_prefix(doc, prefix) == Some(namespace_url));
+            if valid.is_some() {
+                return valid;
+            }
+            search = e.parent(doc);
+        }
+        None
+    }
+
+    pub fn mut_attributes<'a>(&self, doc: &'a mut Document) -> &'a mut HashMap<String, String> {
+        &mut self.mut_data(doc).attributes
+    }
+
+    /// Gets the namespace of this element.
+    ///
+    /// Shorthand for `self.namespace_for_prefix(doc, self.prefix(doc))`.
+    pub fn namespace<'a>(&self, doc: &'a Document) -> Option<&'a str> {
+        self.namespace_for_prefix(doc, self.prefix(doc))
+    }
+
+    /// Get the namespace url and the local name of this element.
+    ///
+    /// The namespace is resolved using [`Element::namespace`]. It is `None` if the element
+    /// is in no namespace, or if its prefix is not declared.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <ns:root xmlns:ns="http://ns">
+    ///     <child />
+    /// </ns:root>
+    /// "#).unwrap();
+    ///
+    /// let root = doc.root_element().unwrap();
+    /// let child = root.child_elements(&doc)[0];
+    /// assert_eq!(root.qualified_name(&doc), (Some("http://ns".to_string()), "root".to_string()));
+    /// assert_eq!(child.qualified_name(&doc), (None, "child".to_string()));
+    /// assert_eq!(root.expanded_name(&doc), "{http://ns}root");
+    /// assert_eq!(child.expanded_name(&doc), "child");
+    /// ```
+    pub fn qualified_name(&self, doc: &Document) -> (Option<String>, String) {
+        let namespace = self
+            .namespace(doc)
+            .filter(|url| !url.is_empty())
+            .map(|url| url.to_string());
+        (namespace, self.name(doc).to_string())
+    }
+
+    /// Get the name of this element in the `{namespace}name` notation (also known as
+    /// "Clark notation"). If the element is in no namespace, only its local name is returned.
+    ///
+    /// See also [`Element::qualified_name`].
+    pub fn expanded_name(&self, doc: &Document) -> String {
+        match self.qualified_name(doc) {
+            (Some(namespace), name) => format!("{{{}}}{}", namespace, name),
+            (None, name) => name,
+        }
+    }
+
+    /// Gets HashMap of `xmlns:prefix=namespace` declared in this element's attributes.
+    ///
+    /// Default namespace has empty string as key.
+    pub fn namespace_decls<'a>(&self, doc: &'a Document) -> &'a HashMap<String, String> {
+        &self.data(doc).namespace_decls
+    }
+
+    pub fn mut_namespace_decls<'a>(
+        &self,
+        doc: &'a mut Document,
+    ) -> &'a mut HashMap<String, String> {
+        &mut self.mut_data(doc).namespace_decls
+    }
+
+    pub fn set_namespace_decl<S, T>(&self, doc: &mut Document, prefix: S, namespace: T)
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.mut_namespace_decls(doc)
+            .insert(prefix.into(), namespace.into());
+    }
+
+    /// Declare the default namespace (`xmlns="namespace"`) on this element.
+    /// Same as `self.set_namespace_decl(doc, "", namespace)`.
+    pub fn set_default_namespace<S: Into<String>>(&self, doc: &mut Document, namespace: S) {
+        self.set_namespace_decl(doc, "", namespace);
+    }
+
+    /// Put this element into the namespace `namespace`: declares `xmlns:prefix="namespace"`
+    /// on this element and sets its prefix to `prefix`. For an empty `prefix`, declares
+    /// the default namespace (`xmlns="namespace"`) and removes the element's prefix.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidName`]: `prefix` is not a valid XML name, or contains a `:`.
+    ///   Nothing is changed in this case.
+    pub fn set_namespace(&self, doc: &mut Document, prefix: &str, namespace: &str) -> Result<()> {
+        self.set_prefix(doc, prefix)?;
+        self.set_namespace_decl(doc, prefix, namespace);
+        Ok(())
+    }
+
+    /// Get namespace value given prefix, for this element.
     /// "xml" and "xmlns" returns its default namespace.
     ///
     /// This method can return an empty namespace, but only for an empty prefix assuming
@@ -471,7 +1225,7 @@ impl Element {
         let prefix = self.closest_prefix(doc, namespace_url);
         if let Some(prefix) = prefix {
             let prefix = prefix.to_string();
-            self.set_prefix(doc, prefix.as_str());
+            self.set_prefix(doc, prefix.as_str()).ok()?;
             Some(prefix)
         } else {
             None
@@ -493,12 +1247,131 @@ impl Element {
         buf
     }
 
+    /// Concatenate only the direct [`Node::Text`], [`Node::CData`] and [`Node::RawText`]
+    /// (unescaped) children of this element, excluding the text of child elements.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <p>a<b>x</b>c<![CDATA[d]]></p>
+    /// "#).unwrap();
+    /// let p = doc.root_element().unwrap();
+    /// assert_eq!(p.own_text(&doc), "acd");
+    /// assert_eq!(p.text_content(&doc), "axcd");
+    /// ```
+    pub fn own_text(&self, doc: &Document) -> String {
+        let mut buf = String::new();
+        for child in self.children(doc) {
+            if let Node::Text(_) | Node::CData(_) | Node::RawText(_) = child {
+                child.build_text_content(doc, &mut buf);
+            }
+        }
+        buf
+    }
+
+    /// Same as [`Element::text_content`], but leading and trailing whitespace is removed
+    /// and every sequence of whitespace is replaced by a single space.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, ReadOptions};
+    ///
+    /// let opts = ReadOptions { trim_text: false, ..ReadOptions::default() };
+    /// let doc = Document::parse_str_with_opts(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <name>
+    ///     Lewis  <b>Carroll</b>
+    /// </name>
+    /// "#, opts).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.text_content(&doc), "\n    Lewis  Carroll\n");
+    /// assert_eq!(root.trimmed_text_content(&doc), "Lewis Carroll");
+    /// ```
+    pub fn trimmed_text_content(&self, doc: &Document) -> String {
+        let text = self.text_content(doc);
+        let words: Vec<&str> = text
+            .split([' ', '\t', '\r', '\n'])
+            .filter(|word| !word.is_empty())
+            .collect();
+        words.join(" ")
+    }
+
     /// Clears all its children and inserts a [`Node::Text`] with given text.
     pub fn set_text_content<S: Into<String>>(&self, doc: &mut Document, text: S) {
         self.clear_children(doc);
         let node = Node::Text(text.into());
         self.push_child(doc, node).unwrap();
     }
+
+    /// Clears all its children and inserts a [`Node::CData`] with given text.
+    ///
+    /// The content can contain `]]>`, it is split across two CDATA sections when written.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::new();
+    /// let container = doc.container();
+    /// let script = Element::build("script").push_to(&mut doc, container);
+    /// script.set_cdata_content(&mut doc, "if (a[b[0]]>c) {}");
+    /// assert_eq!(
+    ///     script.outer_xml(&doc).unwrap(),
+    ///     "<script><![CDATA[if (a[b[0]]]]><![CDATA[>c) {}]]></script>"
+    /// );
+    /// ```
+    pub fn set_cdata_content<S: Into<String>>(&self, doc: &mut Document, text: S) {
+        self.clear_children(doc);
+        let node = Node::CData(text.into());
+        self.push_child(doc, node).unwrap();
+    }
+
+    /// Turn all [`Node::Text`] children of this element into [`Node::CData`] with the
+    /// same content, see [`Node::text_to_cdata`]. Descendants are not modified.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <code>a &lt; b<br/>c &amp;&amp; d</code>
+    /// "#).unwrap();
+    /// let code = doc.root_element().unwrap();
+    /// code.wrap_text_in_cdata(&mut doc);
+    /// let cdata: Vec<&str> = code.cdata_children(&doc).collect();
+    /// assert_eq!(cdata, vec!["a < b", "c && d"]);
+    /// ```
+    pub fn wrap_text_in_cdata(&self, doc: &mut Document) {
+        for node in self.mut_data(doc).children.iter_mut() {
+            if let Node::Text(text) = node {
+                *node = Node::CData(std::mem::take(text));
+            }
+        }
+    }
+
+    /// Append text after the existing children. If the last child is a [`Node::Text`],
+    /// the text is appended to it, otherwise a new [`Node::Text`] is pushed.
+    ///
+    /// Unlike [`Element::set_text_content`], existing children are never removed or modified
+    /// (except for the last text node).
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::new();
+    /// let elem = Element::build("p").text_content("Hello").finish(&mut doc);
+    /// elem.append_text(&mut doc, ", world");
+    /// assert_eq!(elem.children(&doc).len(), 1);
+    /// Element::build("br").push_to(&mut doc, elem);
+    /// elem.append_text(&mut doc, "!");
+    /// assert_eq!(elem.children(&doc).len(), 3);
+    /// assert_eq!(elem.text_content(&doc), "Hello, world!");
+    /// ```
+    pub fn append_text(&self, doc: &mut Document, text: &str) {
+        let children = &mut self.mut_data(doc).children;
+        if let Some(Node::Text(last)) = children.last_mut() {
+            last.push_str(text);
+        } else {
+            children.push(Node::Text(text.to_string()));
+        }
+    }
 }
 
 /// Below are methods related to finding nodes in tree.
@@ -538,21 +1411,138 @@ impl Element {
         !self.children(doc).is_empty()
     }
 
+    /// Returns `true` if the element has no children, or only has a single empty
+    /// [`Node::Text`] (which is how [`crate::ReadOptions::empty_text_node`] represents
+    /// `<tag></tag>`). That is, the element has no content and can be written as `<tag/>`.
+    ///
+    /// Use [`Element::is_force_expanded`] to find out which form was used in the parsed document.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a/><b></b><c>text</c></root>
+    /// "#).unwrap();
+    /// let children = doc.root_element().unwrap().child_elements(&doc);
+    /// let candidates: Vec<bool> = children.iter().map(|e| e.is_self_closing_candidate(&doc)).collect();
+    /// assert_eq!(candidates, vec![true, true, false]);
+    /// let expanded: Vec<bool> = children.iter().map(|e| e.is_force_expanded(&doc)).collect();
+    /// assert_eq!(expanded, vec![false, true, false]);
+    /// ```
+    pub fn is_self_closing_candidate(&self, doc: &Document) -> bool {
+        match self.children(doc).as_slice() {
+            [] => true,
+            [Node::Text(text)] => text.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the element is written as `<tag></tag>` even when it has no children.
+    ///
+    /// The parser sets this for elements written as `<tag></tag>` without any content,
+    /// if [`crate::ReadOptions::empty_text_node`] or [`crate::ReadOptions::preserve_formatting`]
+    /// is set.
+    pub fn is_force_expanded(&self, doc: &Document) -> bool {
+        self.data(doc).force_expanded
+    }
+
+    /// Set whether the element is written as `<tag></tag>` (`true`) or as `<tag/>` (`false`)
+    /// when it has no children. Elements with children, including a single empty
+    /// [`Node::Text`], are always written as `<tag>...</tag>`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::new();
+    /// let elem = Element::new(&mut doc, "script");
+    /// assert_eq!(elem.outer_xml(&doc).unwrap(), "<script/>");
+    /// elem.set_force_expanded(&mut doc, true);
+    /// assert_eq!(elem.outer_xml(&doc).unwrap(), "<script></script>");
+    /// ```
+    pub fn set_force_expanded(&self, doc: &mut Document, yes: bool) {
+        self.mut_data(doc).force_expanded = yes;
+    }
+
     /// Get only child [`Element`]s of this element.
     ///
-    /// This calls `.children().iter().filter_map().collect()`.
-    /// Use [`Element::children()`] if performance is important.
+    /// This calls `.child_elements_iter().collect()`.
+    /// Use [`Element::child_elements_iter()`] if performance is important.
     pub fn child_elements(&self, doc: &Document) -> Vec<Element> {
+        self.child_elements_iter(doc).collect()
+    }
+
+    /// Iterate over child [`Element`]s of this element, without allocating a `Vec`.
+    pub fn child_elements_iter<'a>(&self, doc: &'a Document) -> impl Iterator<Item = Element> + 'a {
         self.children(doc)
             .iter()
-            .filter_map(|node| {
-                if let Node::Element(elemid) = node {
-                    Some(*elemid)
-                } else {
-                    None
-                }
-            })
-            .collect()
+            .filter_map(|node| node.as_element())
+    }
+
+    /// Get the child node at `index`, or `None` if there are not enough children.
+    pub fn child_at<'a>(&self, doc: &'a Document, index: usize) -> Option<&'a Node> {
+        self.children(doc).get(index)
+    }
+
+    /// Get the first child [`Element`] of this element, skipping other nodes.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root>text<a/><b/><!-- comment --></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.child_at(&doc, 0).and_then(|n| n.as_text()), Some("text"));
+    /// assert_eq!(root.first_child_element(&doc), root.find(&doc, "a"));
+    /// assert_eq!(root.last_child_element(&doc), root.find(&doc, "b"));
+    /// ```
+    pub fn first_child_element(&self, doc: &Document) -> Option<Element> {
+        self.child_elements_iter(doc).next()
+    }
+
+    /// Get the last child [`Element`] of this element, skipping other nodes.
+    pub fn last_child_element(&self, doc: &Document) -> Option<Element> {
+        self.children(doc)
+            .iter()
+            .rev()
+            .find_map(|node| node.as_element())
+    }
+
+    /// Iterate over the content of child [`Node::Text`]s of this element.
+    ///
+    /// [`Node::RawText`] children are not included, see [`Element::own_text`].
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root>a<!--x--><b/>c<![CDATA[d]]><?pi e?></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(root.text_children(&doc).collect::<Vec<_>>(), vec!["a", "c"]);
+    /// assert_eq!(root.comment_children(&doc).collect::<Vec<_>>(), vec!["x"]);
+    /// assert_eq!(root.cdata_children(&doc).collect::<Vec<_>>(), vec!["d"]);
+    /// assert_eq!(root.pi_children(&doc).collect::<Vec<_>>(), vec!["pi e"]);
+    /// ```
+    pub fn text_children<'a>(&self, doc: &'a Document) -> impl Iterator<Item = &'a str> + 'a {
+        self.children(doc).iter().filter_map(|node| node.as_text())
+    }
+
+    /// Iterate over the content of child [`Node::Comment`]s of this element.
+    pub fn comment_children<'a>(&self, doc: &'a Document) -> impl Iterator<Item = &'a str> + 'a {
+        self.children(doc)
+            .iter()
+            .filter_map(|node| node.as_comment())
+    }
+
+    /// Iterate over the content of child [`Node::CData`]s of this element.
+    pub fn cdata_children<'a>(&self, doc: &'a Document) -> impl Iterator<Item = &'a str> + 'a {
+        self.children(doc).iter().filter_map(|node| node.as_cdata())
+    }
+
+    /// Iterate over the content of child [`Node::PI`]s of this element.
+    pub fn pi_children<'a>(&self, doc: &'a Document) -> impl Iterator<Item = &'a str> + 'a {
+        self.children(doc).iter().filter_map(|node| node.as_pi())
     }
 
     /// Get child [`Element`]s recursively. (i.e. includes its child element's child elements)
@@ -569,23 +1559,204 @@ impl Element {
             .collect()
     }
 
+    /// Get descendant [`Element`]s in breadth-first (level) order: first all child elements,
+    /// then all grandchildren, and so on. Elements of the same level are in document order.
+    ///
+    /// This contains the same elements as [`Element::child_elements_recursive`], which uses
+    /// document (depth-first, pre-) order instead.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a><c/></a><b><d/></b></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let bfs: Vec<&str> = root
+    ///     .descendant_elements_bfs(&doc)
+    ///     .iter()
+    ///     .map(|e| e.name(&doc))
+    ///     .collect();
+    /// assert_eq!(bfs, vec!["a", "b", "c", "d"]);
+    /// let dfs: Vec<&str> = root
+    ///     .child_elements_recursive(&doc)
+    ///     .iter()
+    ///     .map(|e| e.name(&doc))
+    ///     .collect();
+    /// assert_eq!(dfs, vec!["a", "c", "b", "d"]);
+    /// ```
+    pub fn descendant_elements_bfs(&self, doc: &Document) -> Vec<Element> {
+        let mut result: Vec<Element> = self.child_elements(doc);
+        let mut next = 0;
+        while next < result.len() {
+            let elem = result[next];
+            result.extend(elem.child_elements_iter(doc));
+            next += 1;
+        }
+        result
+    }
+
+    /// Returns `true` if the local name of this element is `name` (if given), and every
+    /// attribute constraint holds. A constraint `(attr, None)` requires that attribute `attr`
+    /// (full name) is present, `(attr, Some(value))` requires that its value equals `value`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><item id="a" class="x"/><item id="b"/><other class="x"/></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let matching: Vec<_> = root
+    ///     .child_elements_iter(&doc)
+    ///     .filter(|e| e.matches(&doc, Some("item"), &[("id", None), ("class", Some("x"))]))
+    ///     .collect();
+    /// assert_eq!(matching.len(), 1);
+    /// assert_eq!(matching[0].attribute(&doc, "id"), Some("a"));
+    /// let with_class = root.child_elements_iter(&doc).filter(|e| e.matches(&doc, None, &[("class", None)]));
+    /// assert_eq!(with_class.count(), 2);
+    /// ```
+    pub fn matches(
+        &self,
+        doc: &Document,
+        name: Option<&str>,
+        attrs: &[(&str, Option<&str>)],
+    ) -> bool {
+        if let Some(name) = name {
+            if self.name(doc) != name {
+                return false;
+            }
+        }
+        attrs.iter().all(
+            |(attr, expected)| match (self.attribute(doc, attr), expected) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(value), Some(expected)) => value == *expected,
+            },
+        )
+    }
+
+    /// Returns `true` if `other` has the same content as this element: the same full name,
+    /// attributes, namespace declarations, and recursively the same children.
+    ///
+    /// Unlike `==`, which checks whether two handles refer to the same element, this compares
+    /// the content. The order of attributes and whether an empty element is written as
+    /// `<tag></tag>` (see [`Element::is_force_expanded`]) are not compared.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a x="1" y="2">text</a><a y="2" x="1">text</a><a x="1">text</a></root>
+    /// "#).unwrap();
+    /// let items = doc.root_element().unwrap().child_elements(&doc);
+    /// assert_ne!(items[0], items[1]);
+    /// assert!(items[0].structural_eq(&doc, items[1]));
+    /// assert!(!items[0].structural_eq(&doc, items[2]));
+    /// ```
+    pub fn structural_eq(&self, doc: &Document, other: Element) -> bool {
+        let (a, b) = (self.data(doc), other.data(doc));
+        a.full_name == b.full_name
+            && a.attributes == b.attributes
+            && a.namespace_decls == b.namespace_decls
+            && a.children.len() == b.children.len()
+            && a.children
+                .iter()
+                .zip(b.children.iter())
+                .all(|pair| match pair {
+                    (Node::Element(a), Node::Element(b)) => a.structural_eq(doc, *b),
+                    (Node::Text(a), Node::Text(b))
+                    | (Node::Comment(a), Node::Comment(b))
+                    | (Node::CData(a), Node::CData(b))
+                    | (Node::PI(a), Node::PI(b))
+                    | (Node::DocType(a), Node::DocType(b))
+                    | (Node::RawText(a), Node::RawText(b)) => a == b,
+                    _ => false,
+                })
+    }
+
     /// Find first direct child element with name `name`.
     pub fn find(&self, doc: &Document, name: &str) -> Option<Element> {
-        self.children(doc)
-            .iter()
-            .filter_map(|n| n.as_element())
-            .find(|e| e.name(doc) == name)
+        self.child_elements_iter(doc).find(|e| e.name(doc) == name)
     }
 
     /// Find all direct child elements with name `name`.
+    ///
+    /// Use [`Element::find_all_iter()`] if performance is important.
     pub fn find_all(&self, doc: &Document, name: &str) -> Vec<Element> {
-        self.children(doc)
-            .iter()
-            .filter_map(|n| n.as_element())
-            .filter(|e| e.name(doc) == name)
+        self.find_all_iter(doc, name).collect()
+    }
+
+    /// Iterate over all direct child elements with name `name`, without allocating a `Vec`.
+    pub fn find_all_iter<'a>(
+        &self,
+        doc: &'a Document,
+        name: &'a str,
+    ) -> impl Iterator<Item = Element> + 'a {
+        self.child_elements_iter(doc)
+            .filter(move |e| e.name(doc) == name)
+    }
+
+    /// Find first direct child element whose name matches `name`, ignoring case.
+    ///
+    /// Only ASCII letters are compared case-insensitively (see [`str::eq_ignore_ascii_case`]),
+    /// other characters must match exactly. That is, `Item` matches `ITEM`, but `Größe`
+    /// does not match `GRÖSSE`.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <list><Item/><item/><ITEM/><other/></list>
+    /// "#).unwrap();
+    /// let list = doc.root_element().unwrap();
+    /// assert!(list.find(&doc, "ITEM").unwrap() != list.find(&doc, "Item").unwrap());
+    /// assert_eq!(list.find_ignore_case(&doc, "ITEM"), list.find(&doc, "Item"));
+    /// assert_eq!(list.find_all_ignore_case(&doc, "item").len(), 3);
+    /// ```
+    pub fn find_ignore_case(&self, doc: &Document, name: &str) -> Option<Element> {
+        self.child_elements_iter(doc)
+            .find(|e| e.name(doc).eq_ignore_ascii_case(name))
+    }
+
+    /// Find all direct child elements whose name matches `name`, ignoring case.
+    ///
+    /// Only ASCII letters are compared case-insensitively, see [`Element::find_ignore_case`].
+    pub fn find_all_ignore_case(&self, doc: &Document, name: &str) -> Vec<Element> {
+        self.child_elements_iter(doc)
+            .filter(|e| e.name(doc).eq_ignore_ascii_case(name))
             .collect()
     }
 
+    /// Find the first direct child element whose full name (including the prefix) is
+    /// exactly `full_name`, e.g. `"ns1:child"`.
+    ///
+    /// Unlike [`Element::find_quantified`], the prefix is compared as written, without
+    /// resolving it to a namespace. An unprefixed `full_name` only matches unprefixed children.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <parent xmlns:ns1="http://ns1" xmlns:ns2="http://ns2">
+    ///     <ns2:child id="1"/>
+    ///     <ns1:child id="2"/>
+    ///     <child id="3"/>
+    /// </parent>
+    /// "#).unwrap();
+    ///
+    /// let root = doc.root_element().unwrap();
+    /// let child = root.find_by_prefixed_name(&doc, "ns1:child").unwrap();
+    /// assert_eq!(child.attribute(&doc, "id"), Some("2"));
+    /// let child = root.find_by_prefixed_name(&doc, "child").unwrap();
+    /// assert_eq!(child.attribute(&doc, "id"), Some("3"));
+    /// assert_eq!(root.find_by_prefixed_name(&doc, "ns3:child"), None);
+    /// ```
+    pub fn find_by_prefixed_name(&self, doc: &Document, full_name: &str) -> Option<Element> {
+        self.child_elements_iter(doc)
+            .find(|e| e.full_name(doc) == full_name)
+    }
+
     /// A helper method that identifies child based on namespace if the namespace is
     /// declared directly on this child.
     fn has_self_declared_namespace(
@@ -799,6 +1970,11 @@ impl Element {
     /// when the sub-tree contains elements with no prefix and there is no default namespace url
     /// declared by the parents.
     ///
+    /// # Errors
+    ///
+    /// - [`Error::UndeclaredPrefix`]: An element in the sub-tree uses a prefix which is not
+    ///   declared. Use [`Element::undeclared_prefixes`] to find all such prefixes.
+    ///
     /// ```rust
     /// use std::collections::HashMap;
     /// use biodivine_xml_doc::Document;
@@ -814,7 +1990,7 @@ impl Element {
     ///
     /// let root = doc.root_element().unwrap();
     /// let child = root.child_elements(&doc)[0];
-    /// let declarations = child.collect_external_namespace_decls(&doc);
+    /// let declarations = child.collect_external_namespace_decls(&doc).unwrap();
     /// // The result should contain "" and "ns1". "ns2" is-redeclared on child, so is not needed.
     /// let expected = HashMap::from([
     ///     ("".to_string(), "http://ns1".to_string()),
@@ -823,7 +1999,10 @@ impl Element {
     /// assert_eq!(declarations.len(), 2);
     /// assert_eq!(declarations, expected);
     /// ```
-    pub fn collect_external_namespace_decls(&self, doc: &Document) -> HashMap<String, String> {
+    pub fn collect_external_namespace_decls(
+        &self,
+        doc: &Document,
+    ) -> Result<HashMap<String, String>> {
         /// Collect all prefixes within the element subtree that are not declared
         /// within the sub-tree itself.
         fn collect_prefixes<'a>(
@@ -851,7 +2030,7 @@ impl Element {
                     my_known_prefixes.insert(prefix.as_str());
                 }
                 let my_prefix = e.prefix(doc);
-                if !known_prefixes.contains(my_prefix) {
+                if !my_known_prefixes.contains(my_prefix) {
                     unknown_prefixes.insert(my_prefix);
                 }
                 for child in e.child_elements(doc) {
@@ -868,11 +2047,56 @@ impl Element {
             .into_iter()
             .map(|prefix| {
                 let Some(namespace) = self.namespace_for_prefix(doc, prefix) else {
-                    panic!("Invalid XML document. Prefix `{}` not declared.", prefix);
+                    return Err(Error::UndeclaredPrefix(prefix.to_string()));
                 };
-                (prefix.to_string(), namespace.to_string())
+                Ok((prefix.to_string(), namespace.to_string()))
             })
-            .collect::<HashMap<_, _>>()
+            .collect::<Result<HashMap<_, _>>>()
+    }
+
+    /// Find all namespace prefixes which are used in the sub-tree of this `Element` (including
+    /// the element itself), but do not resolve to any namespace declaration that is in scope.
+    ///
+    /// Both element prefixes and attribute prefixes are checked. Each prefix is reported once,
+    /// in the order in which it is first used in the document. The empty prefix is never
+    /// reported, since unprefixed elements can always be in no namespace.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <sbml xmlns:layout="http://layout">
+    ///     <layout:layout/>
+    /// </sbml>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert!(root.undeclared_prefixes(&doc).is_empty());
+    ///
+    /// Element::build("render:style")
+    ///     .attribute("fbc:id", "s1")
+    ///     .attribute("layout:id", "s2")
+    ///     .push_to(&mut doc, root);
+    /// assert_eq!(root.undeclared_prefixes(&doc), vec!["render", "fbc"]);
+    /// ```
+    pub fn undeclared_prefixes(&self, doc: &Document) -> Vec<String> {
+        let mut elements = vec![*self];
+        elements.append(&mut self.child_elements_recursive(doc));
+
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+        for e in elements {
+            let prefixes = std::iter::once(e.prefix(doc)).chain(
+                e.attributes_sorted(doc)
+                    .into_iter()
+                    .map(|(name, _)| Element::separate_prefix_name(name).0),
+            );
+            for prefix in prefixes {
+                if e.namespace_for_prefix(doc, prefix).is_none() && seen.insert(prefix) {
+                    result.push(prefix.to_string());
+                }
+            }
+        }
+        result
     }
 
     /// Find the "closest" namespace prefix which is associated with the given `namespace_url`.
@@ -881,57 +2105,272 @@ impl Element {
     /// Otherwise, the closest parent with the declared namespace is found and this prefix
     /// is returned. If the namespace is not declared for this element, `None` is returned.
     ///
-    /// If the "closest" element has multiple declarations of the namespace in question,
-    /// the lexicographically first prefix is return (i.e. compared through standard
-    /// string ordering).
+    /// If the "closest" element has multiple declarations of the namespace in question,
+    /// the lexicographically first prefix is return (i.e. compared through standard
+    /// string ordering).
+    ///
+    /// You can use empty namespace url to signify "no namespace", in which case the method
+    /// can only return an empty prefix, but it can also return `None` if there is a default
+    /// namespace which prevents you from having "no namespace" on this element.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <parent xmlns="http://ns1" xmlns:ns1="http://ns1" xmlns:ns2="http://ns2">
+    ///     <child xmlns:ns="http://ns2" />
+    /// </parent>
+    /// "#).unwrap();
+    ///
+    /// let root = doc.root_element().unwrap();
+    /// let child = root.child_elements(&doc)[0];
+    /// assert_eq!(root.closest_prefix(&doc, "http://ns1"), Some(""));
+    /// assert_eq!(root.closest_prefix(&doc, "http://ns2"), Some("ns2"));
+    /// assert_eq!(child.closest_prefix(&doc, "http://ns1"), Some(""));
+    /// assert_eq!(child.closest_prefix(&doc, "http://ns2"), Some("ns"));
+    /// ```
+    ///
+    pub fn closest_prefix<'a>(&self, doc: &'a Document, namespace_url: &str) -> Option<&'a str> {
+        let mut search = *self;
+        loop {
+            let mut candidate: Option<&str> = None;
+            for (prefix, url) in search.namespace_decls(doc) {
+                if url == namespace_url {
+                    if let Some(current) = candidate {
+                        if prefix.as_str() < current {
+                            candidate = Some(prefix);
+                        }
+                    } else {
+                        candidate = Some(prefix);
+                    }
+                }
+            }
+            if candidate.is_some() {
+                return candidate;
+            }
+            if let Some(parent) = search.parent(doc) {
+                search = parent;
+            } else if namespace_url.is_empty() {
+                return Some("");
+            } else {
+                return None;
+            }
+        }
+    }
+
+    /// Remove all namespace declarations in the sub-tree of this `Element` (including
+    /// the element itself) which re-declare a prefix with the exact namespace url that
+    /// is already in scope.
+    ///
+    /// Declarations that *shadow* a prefix with a different url are always kept, hence
+    /// the namespace of every element and attribute remains unchanged. A default namespace
+    /// declaration `xmlns=""` is also removed when no default namespace is in scope.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <parent xmlns="http://ns1" xmlns:ns2="http://ns2">
+    ///     <child xmlns="http://ns1" xmlns:ns2="http://other">
+    ///         <ns2:child xmlns:ns2="http://other"/>
+    ///     </child>
+    /// </parent>
+    /// "#).unwrap();
+    ///
+    /// let root = doc.root_element().unwrap();
+    /// root.remove_redundant_namespace_decls(&mut doc);
+    /// let child = root.child_elements(&doc)[0];
+    /// let inner = child.child_elements(&doc)[0];
+    /// assert_eq!(root.namespace_decls(&doc).len(), 2);
+    /// // The default namespace is redundant, but `ns2` shadows a different url.
+    /// assert_eq!(child.namespace_decls(&doc).len(), 1);
+    /// assert_eq!(child.namespace_for_prefix(&doc, "ns2"), Some("http://other"));
+    /// assert!(inner.namespace_decls(&doc).is_empty());
+    /// assert_eq!(inner.namespace(&doc), Some("http://other"));
+    /// ```
+    pub fn remove_redundant_namespace_decls(&self, doc: &mut Document) {
+        let mut redundant = Vec::new();
+        let mut elements = vec![*self];
+        elements.append(&mut self.child_elements_recursive(doc));
+        for e in elements {
+            for (prefix, url) in e.namespace_decls(doc) {
+                let in_scope = match e.parent(doc) {
+                    Some(parent) => parent.namespace_for_prefix(doc, prefix),
+                    None if prefix.is_empty() => Some(""),
+                    None => None,
+                };
+                if in_scope == Some(url.as_str()) {
+                    redundant.push((e, prefix.clone()));
+                }
+            }
+        }
+        // Removing a redundant declaration never changes the namespaces that are in scope,
+        // so it is safe to remove them only after the whole sub-tree has been checked.
+        for (e, prefix) in redundant {
+            e.mut_namespace_decls(doc).remove(&prefix);
+        }
+    }
+
+    /// Find the prefixes declared on this `Element` which are not used by the element itself,
+    /// its attributes, or any element or attribute in its sub-tree, sorted. Uses of a prefix
+    /// in a sub-tree where it is declared again (shadowed) do not count.
+    ///
+    /// The default namespace (empty prefix) is used by unprefixed elements, but not by
+    /// unprefixed attributes, which have no namespace. Prefixes referenced only in text
+    /// or attribute values (e.g. `xsi:type="xs:string"`) cannot be detected and are reported
+    /// as unused.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <a:root xmlns="http://d" xmlns:a="http://a" xmlns:b="http://b" xmlns:c="http://c">
+    ///     <a:child c:attr="1"/>
+    ///     <inner xmlns:b="http://other"><b:item/></inner>
+    /// </a:root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// // `b` is only used where it is shadowed.
+    /// assert_eq!(root.unused_namespace_decls(&doc), vec!["b"]);
+    /// ```
+    pub fn unused_namespace_decls(&self, doc: &Document) -> Vec<String> {
+        fn is_used(e: Element, doc: &Document, prefix: &str, shadowing: bool) -> bool {
+            if shadowing && e.namespace_decls(doc).contains_key(prefix) {
+                return false;
+            }
+            if e.prefix(doc) == prefix {
+                return true;
+            }
+            let in_attributes = !prefix.is_empty()
+                && e.attributes(doc)
+                    .keys()
+                    .any(|name| Element::separate_prefix_name(name).0 == prefix);
+            in_attributes
+                || e.child_elements_iter(doc)
+                    .any(|child| is_used(child, doc, prefix, true))
+        }
+
+        let mut unused: Vec<String> = self
+            .namespace_decls(doc)
+            .keys()
+            .filter(|prefix| !is_used(*self, doc, prefix, false))
+            .cloned()
+            .collect();
+        unused.sort_unstable();
+        unused
+    }
+}
+
+/// Below are methods for writing an element sub-tree as xml, and for replacing it with parsed xml.
+///
+/// The written result is a self-contained fragment: namespaces used by the sub-tree
+/// but declared by its ancestors are declared on the written elements.
+impl Element {
+    /// Write this element and its descendants into a string, using the given options.
+    ///
+    /// This is the same as [`Element::outer_xml`], except that [`WriteOptions`] can be
+    /// supplied. [`WriteOptions::write_decl`] is ignored.
+    ///
+    /// Returns [`Error::UndeclaredPrefix`] if the sub-tree uses an undeclared prefix,
+    /// and [`Error::InvalidComment`] or [`Error::InvalidProcessingInstruction`] if it contains
+    /// a comment or a processing instruction which cannot be written.
+    pub fn write_str(&self, doc: &Document, opts: WriteOptions) -> Result<String> {
+        doc.write_fragment_str(&[Node::Element(*self)], opts)
+    }
+
+    /// Returns a value which implements [`std::fmt::Display`] by writing this element
+    /// using [`Element::outer_xml`].
+    ///
+    /// # Panics
+    ///
+    /// Formatting the value panics if the element cannot be written, i.e. when the sub-tree
+    /// uses an undeclared prefix, or contains a comment with `--` or a processing instruction
+    /// with `?>`. Use [`Element::outer_xml`] to handle these errors.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><child>text</child></root>
+    /// "#).unwrap();
+    /// let child = doc.root_element().unwrap().child_elements(&doc)[0];
+    /// assert_eq!(format!("{}", child.display(&doc)), "<child>text</child>");
+    /// ```
+    pub fn display<'a>(&'a self, doc: &'a Document) -> impl std::fmt::Display + 'a {
+        ElementDisplay {
+            element: *self,
+            doc,
+        }
+    }
+
+    /// Write this element, including its own tags, into a string.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root xmlns:ns="http://ns"><ns:child>text</ns:child></root>
+    /// "#).unwrap();
+    /// let child = doc.root_element().unwrap().child_elements(&doc)[0];
+    /// assert_eq!(
+    ///     child.outer_xml(&doc).unwrap(),
+    ///     r#"<ns:child xmlns:ns="http://ns">text</ns:child>"#
+    /// );
+    /// ```
+    pub fn outer_xml(&self, doc: &Document) -> Result<String> {
+        self.write_str(doc, WriteOptions::default())
+    }
+
+    /// Write the children of this element into a string, without the element's own tags.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root xmlns:ns="http://ns">text<ns:child/></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// assert_eq!(
+    ///     root.inner_xml(&doc).unwrap(),
+    ///     r#"text<ns:child xmlns:ns="http://ns"/>"#
+    /// );
+    /// ```
+    pub fn inner_xml(&self, doc: &Document) -> Result<String> {
+        doc.write_fragment_str(self.children(doc), WriteOptions::default())
+    }
+
+    /// Parse `xml` as a fragment and replace the children of this element with the parsed nodes.
+    ///
+    /// The fragment does not need an XML declaration and can contain multiple elements
+    /// and text nodes. Text is not trimmed. If parsing fails, the element is not modified.
     ///
-    /// You can use empty namespace url to signify "no namespace", in which case the method
-    /// can only return an empty prefix, but it can also return `None` if there is a default
-    /// namespace which prevents you from having "no namespace" on this element.
+    /// # Errors
+    /// - [`Error::MalformedXML`]: The fragment is not well-formed.
     ///
     /// ```rust
     /// use biodivine_xml_doc::Document;
     ///
     /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
-    /// <parent xmlns="http://ns1" xmlns:ns1="http://ns1" xmlns:ns2="http://ns2">
-    ///     <child xmlns:ns="http://ns2" />
-    /// </parent>
+    /// <root><old/></root>
     /// "#).unwrap();
-    ///
     /// let root = doc.root_element().unwrap();
-    /// let child = root.child_elements(&doc)[0];
-    /// assert_eq!(root.closest_prefix(&doc, "http://ns1"), Some(""));
-    /// assert_eq!(root.closest_prefix(&doc, "http://ns2"), Some("ns2"));
-    /// assert_eq!(child.closest_prefix(&doc, "http://ns1"), Some(""));
-    /// assert_eq!(child.closest_prefix(&doc, "http://ns2"), Some("ns"));
+    /// root.set_inner_xml(&mut doc, "Hello <b>world</b>!").unwrap();
+    /// assert_eq!(root.inner_xml(&doc).unwrap(), "Hello <b>world</b>!");
+    /// assert_eq!(root.find(&doc, "b").unwrap().parent(&doc), Some(root));
     /// ```
-    ///
-    pub fn closest_prefix<'a>(&self, doc: &'a Document, namespace_url: &str) -> Option<&'a str> {
-        let mut search = *self;
-        loop {
-            let mut candidate: Option<&str> = None;
-            for (prefix, url) in search.namespace_decls(doc) {
-                if url == namespace_url {
-                    if let Some(current) = candidate {
-                        if prefix.as_str() < current {
-                            candidate = Some(prefix);
-                        }
-                    } else {
-                        candidate = Some(prefix);
-                    }
-                }
-            }
-            if candidate.is_some() {
-                return candidate;
-            }
-            if let Some(parent) = search.parent(doc) {
-                search = parent;
-            } else if namespace_url.is_empty() {
-                return Some("");
-            } else {
-                return None;
-            }
+    pub fn set_inner_xml(&self, doc: &mut Document, xml: &str) -> Result<()> {
+        let opts = ReadOptions {
+            trim_text: false,
+            require_decl: false,
+            ..ReadOptions::default()
+        };
+        let fragment = Document::parse_str_with_opts(xml, opts)?;
+        self.clear_children(doc);
+        for node in fragment.container().children(&fragment) {
+            let node = node.deep_copy(&fragment, doc);
+            self.push_child(doc, node)?;
         }
+        Ok(())
     }
 }
 
@@ -945,10 +2384,11 @@ impl Element {
     /// Equivalent to `vec.push()`.
     /// # Errors
     /// - [`Error::HasAParent`]: When you want to replace an element's parent with another,
-    /// call `element.detatch()` to make it parentless first.
-    /// This is to make it explicit that you are changing an element's parent, not adding another.
+    ///   call `element.detatch()` to make it parentless first.
+    ///   This is to make it explicit that you are changing an element's parent, not adding another.
     /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
-    pub fn push_child(&self, doc: &mut Document, node: Node) -> Result<()> {
+    pub fn push_child(&self, doc: &mut Document, node: impl Into<Node>) -> Result<()> {
+        let node = node.into();
         if let Node::Element(elem) = node {
             if elem.is_container() {
                 return Err(Error::ContainerCannotMove);
@@ -967,8 +2407,8 @@ impl Element {
     ///
     /// # Errors
     /// - [`Error::HasAParent`]: When you want to replace an element's parent with another,
-    /// call `element.detatch()` to make it parentless first.
-    /// This is to make it explicit that you are changing an element's parent, not adding another.
+    ///   call `element.detatch()` to make it parentless first.
+    ///   This is to make it explicit that you are changing an element's parent, not adding another.
     /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
     pub fn push_to(&self, doc: &mut Document, parent: Element) -> Result<()> {
         parent.push_child(doc, self.as_node())
@@ -982,10 +2422,16 @@ impl Element {
     ///
     /// # Errors
     /// - [`Error::HasAParent`]: When you want to replace an element's parent with another,
-    /// call `element.detatch()` to make it parentless first.
-    /// This is to make it explicit that you are changing an element's parent, not adding another.
+    ///   call `element.detatch()` to make it parentless first.
+    ///   This is to make it explicit that you are changing an element's parent, not adding another.
     /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
-    pub fn insert_child(&self, doc: &mut Document, index: usize, node: Node) -> Result<()> {
+    pub fn insert_child(
+        &self,
+        doc: &mut Document,
+        index: usize,
+        node: impl Into<Node>,
+    ) -> Result<()> {
+        let node = node.into();
         if let Node::Element(elem) = node {
             if elem.is_container() {
                 return Err(Error::ContainerCannotMove);
@@ -1000,6 +2446,205 @@ impl Element {
         Ok(())
     }
 
+    /// Insert `node` as the first child, equivalent to `self.insert_child(doc, 0, node)`.
+    ///
+    /// # Errors
+    /// - [`Error::HasAParent`]: When you want to replace an element's parent with another,
+    ///   call `element.detatch()` to make it parentless first.
+    /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
+    pub fn prepend_child(&self, doc: &mut Document, node: impl Into<Node>) -> Result<()> {
+        self.insert_child(doc, 0, node)
+    }
+
+    /// Insert `node` right before `reference`, which must be a child of this element.
+    ///
+    /// # Errors
+    /// - [`Error::NotAChild`]: `reference` is not a child of this element.
+    /// - [`Error::HasAParent`]: When you want to replace an element's parent with another,
+    ///   call `element.detatch()` to make it parentless first.
+    /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><b/></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let b = root.find(&doc, "b").unwrap();
+    /// let a = Element::new(&mut doc, "a");
+    /// let c = Element::new(&mut doc, "c");
+    /// root.insert_before(&mut doc, b, a.as_node()).unwrap();
+    /// root.insert_after(&mut doc, b, c.as_node()).unwrap();
+    /// assert_eq!(root.outer_xml(&doc).unwrap(), "<root>\n  <a/>\n  <b/>\n  <c/>\n</root>");
+    /// ```
+    pub fn insert_before(
+        &self,
+        doc: &mut Document,
+        reference: Element,
+        node: impl Into<Node>,
+    ) -> Result<()> {
+        let index = self.child_position(doc, reference)?;
+        self.insert_child(doc, index, node)
+    }
+
+    /// Insert `node` right after `reference`, which must be a child of this element.
+    /// See [`Element::insert_before`].
+    ///
+    /// # Errors
+    /// - [`Error::NotAChild`]: `reference` is not a child of this element.
+    /// - [`Error::HasAParent`]: When you want to replace an element's parent with another,
+    ///   call `element.detatch()` to make it parentless first.
+    /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
+    pub fn insert_after(
+        &self,
+        doc: &mut Document,
+        reference: Element,
+        node: impl Into<Node>,
+    ) -> Result<()> {
+        let index = self.child_position(doc, reference)?;
+        self.insert_child(doc, index + 1, node)
+    }
+
+    /// Index of `child` among the children of this element.
+    fn child_position(&self, doc: &Document, child: Element) -> Result<usize> {
+        if child.parent(doc) != Some(*self) {
+            return Err(Error::NotAChild);
+        }
+        self.children(doc)
+            .iter()
+            .position(|n| n.as_element() == Some(child))
+            .ok_or(Error::NotAChild)
+    }
+
+    /// Replace the child at `index` with `node`, and return the replaced child.
+    /// If the replaced child is an element, it is detached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= self.children().len()`.
+    ///
+    /// # Errors
+    /// - [`Error::HasAParent`]: When you want to replace an element's parent with another,
+    ///   call `element.detatch()` to make it parentless first.
+    ///   This is to make it explicit that you are changing an element's parent, not adding another.
+    /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
+    pub fn replace_child(
+        &self,
+        doc: &mut Document,
+        index: usize,
+        node: impl Into<Node>,
+    ) -> Result<Node> {
+        let node = node.into();
+        if let Node::Element(elem) = node {
+            if elem.is_container() {
+                return Err(Error::ContainerCannotMove);
+            }
+            let data = elem.mut_data(doc);
+            if data.parent.is_some() {
+                return Err(Error::HasAParent);
+            }
+            data.parent = Some(*self);
+        }
+        let old = std::mem::replace(&mut self.mut_data(doc).children[index], node);
+        if let Node::Element(elem) = old {
+            elem.mut_data(doc).parent = None;
+        }
+        Ok(old)
+    }
+
+    /// Replace this element in its parent with `replacement`, and detach this element.
+    ///
+    /// # Errors
+    /// - [`Error::HasNoParent`]: This element has no parent (e.g. it is the container).
+    /// - [`Error::HasAParent`]: `replacement` already has a parent,
+    ///   call `replacement.detatch()` first.
+    /// - [`Error::ContainerCannotMove`]: `replacement` is the container.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a/><b/><c/></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let b = root.find(&doc, "b").unwrap();
+    /// let x = Element::new(&mut doc, "x");
+    /// b.replace_with(&mut doc, x).unwrap();
+    /// assert_eq!(root.outer_xml(&doc).unwrap(), "<root>\n  <a/>\n  <x/>\n  <c/>\n</root>");
+    /// assert_eq!(x.parent(&doc), Some(root));
+    /// assert_eq!(b.parent(&doc), None);
+    /// ```
+    pub fn replace_with(&self, doc: &mut Document, replacement: Element) -> Result<()> {
+        let parent = self.parent(doc).ok_or(Error::HasNoParent)?;
+        let index = parent
+            .children(doc)
+            .iter()
+            .position(|n| n.as_element() == Some(*self))
+            .unwrap();
+        parent.replace_child(doc, index, replacement.as_node())?;
+        Ok(())
+    }
+
+    /// Exchange the positions of this element and `other`, which can have different parents.
+    /// Swapping an element with itself does nothing.
+    ///
+    /// # Errors
+    /// - [`Error::ContainerCannotMove`]: One of the elements is the container.
+    /// - [`Error::HasNoParent`]: One of the elements has no parent.
+    /// - [`Error::CyclicMove`]: One of the elements is an ancestor of the other.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a/><b><c/></b></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let a = root.find(&doc, "a").unwrap();
+    /// let b = root.find(&doc, "b").unwrap();
+    /// let c = b.find(&doc, "c").unwrap();
+    /// a.swap_with(&mut doc, c).unwrap();
+    /// assert_eq!(root.outer_xml(&doc).unwrap(), "<root>\n  <c/>\n  <b>\n    <a/>\n  </b>\n</root>");
+    /// assert_eq!(a.parent(&doc), Some(b));
+    /// assert!(b.swap_with(&mut doc, a).is_err());
+    /// ```
+    pub fn swap_with(&self, doc: &mut Document, other: Element) -> Result<()> {
+        if self.is_container() || other.is_container() {
+            return Err(Error::ContainerCannotMove);
+        }
+        let parent = self.parent(doc).ok_or(Error::HasNoParent)?;
+        let other_parent = other.parent(doc).ok_or(Error::HasNoParent)?;
+        if self == &other {
+            return Ok(());
+        }
+        if self.is_ancestor_of(doc, other) || other.is_ancestor_of(doc, *self) {
+            return Err(Error::CyclicMove);
+        }
+        let index = parent.child_position(doc, *self)?;
+        let other_index = other_parent.child_position(doc, other)?;
+        if parent == other_parent {
+            parent.mut_data(doc).children.swap(index, other_index);
+        } else {
+            parent.mut_data(doc).children[index] = other.as_node();
+            other_parent.mut_data(doc).children[other_index] = self.as_node();
+            self.mut_data(doc).parent = Some(other_parent);
+            other.mut_data(doc).parent = Some(parent);
+        }
+        Ok(())
+    }
+
+    fn is_ancestor_of(&self, doc: &Document, elem: Element) -> bool {
+        let mut current = elem.parent(doc);
+        while let Some(parent) = current {
+            if parent == *self {
+                return true;
+            }
+            current = parent.parent(doc);
+        }
+        false
+    }
+
     /// Equivalent to `vec.remove()`.
     ///
     /// # Panics
@@ -1013,6 +2658,29 @@ impl Element {
         node
     }
 
+    /// Remove `child` from the children of this element and return it,
+    /// or return `None` if `child` is not a child of this element.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><a><b/></a></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let a = root.find(&doc, "a").unwrap();
+    /// let b = a.find(&doc, "b").unwrap();
+    /// assert!(root.remove_child_element(&mut doc, b).is_none());
+    /// let removed = root.remove_child_element(&mut doc, a).unwrap();
+    /// assert_eq!(removed.as_element(), Some(a));
+    /// assert_eq!(a.parent(&doc), None);
+    /// assert!(root.children(&doc).is_empty());
+    /// ```
+    pub fn remove_child_element(&self, doc: &mut Document, child: Element) -> Option<Node> {
+        let index = self.child_position(doc, child).ok()?;
+        Some(self.remove_child(doc, index))
+    }
+
     /// Equivalent to `vec.pop()`.
     pub fn pop_child(&self, doc: &mut Document) -> Option<Node> {
         let child = self.mut_data(doc).children.pop();
@@ -1033,6 +2701,127 @@ impl Element {
         removed
     }
 
+    /// Replace all children with `nodes`. The old element children are detached.
+    ///
+    /// All nodes are checked before anything is changed, so if an error is returned,
+    /// the children of this element stay the same.
+    ///
+    /// # Errors
+    /// - [`Error::HasAParent`]: One of the elements in `nodes` already has a parent
+    ///   (or it is in `nodes` more than once). Call `element.detatch()` to make it
+    ///   parentless first, even if it is currently a child of this element.
+    /// - [`Error::ContainerCannotMove`]: The container element's parent must always be None.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::{Document, Element, Error, Node};
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root><old/></root>
+    /// "#).unwrap();
+    /// let root = doc.root_element().unwrap();
+    /// let old = root.find(&doc, "old").unwrap();
+    /// let new = Element::new(&mut doc, "new");
+    ///
+    /// let result = root.set_children(&mut doc, vec![new.as_node(), old.as_node()]);
+    /// assert!(matches!(result, Err(Error::HasAParent)));
+    /// assert_eq!(root.child_elements(&doc), vec![old]);
+    ///
+    /// root.set_children(&mut doc, vec![Node::Text("Hi".to_string()), new.as_node()]).unwrap();
+    /// assert_eq!(root.text_content(&doc), "Hi");
+    /// assert_eq!(root.child_elements(&doc), vec![new]);
+    /// assert_eq!(old.parent(&doc), None);
+    /// ```
+    pub fn set_children(&self, doc: &mut Document, nodes: Vec<Node>) -> Result<()> {
+        let mut seen = HashSet::new();
+        for node in &nodes {
+            if let Node::Element(elem) = node {
+                if elem.is_container() {
+                    return Err(Error::ContainerCannotMove);
+                }
+                if elem.has_parent(doc) || !seen.insert(*elem) {
+                    return Err(Error::HasAParent);
+                }
+            }
+        }
+        self.clear_children(doc);
+        for node in &nodes {
+            if let Node::Element(elem) = node {
+                elem.mut_data(doc).parent = Some(*self);
+            }
+        }
+        self.mut_data(doc).children = nodes;
+        Ok(())
+    }
+
+    /// Equivalent to `vec.swap()`.
+    ///
+    /// Swapping does not change the parent of any node, so this never fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i >= self.children().len()` or `j >= self.children().len()`.
+    pub fn swap_children(&self, doc: &mut Document, i: usize, j: usize) {
+        self.mut_data(doc).children.swap(i, j);
+    }
+
+    /// Reorder the child elements of this element according to the given `key` function.
+    ///
+    /// Only [`Node::Element`] children are reordered: all other nodes (text, comments, etc.)
+    /// keep their original positions, and the sorted elements are placed into the positions
+    /// previously occupied by elements. The sort is stable, so elements with equal keys
+    /// keep their relative order.
+    ///
+    /// ```rust
+    /// use biodivine_xml_doc::Document;
+    ///
+    /// let mut doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+    /// <root>
+    ///     <item id="3"/>
+    ///     <!-- comment -->
+    ///     <item id="1"/>
+    ///     <item id="2"/>
+    /// </root>
+    /// "#).unwrap();
+    ///
+    /// let root = doc.root_element().unwrap();
+    /// root.sort_children_by(&mut doc, |e, doc| e.attribute(doc, "id").map(|id| id.to_string()));
+    /// let ids: Vec<&str> = root
+    ///     .child_elements(&doc)
+    ///     .iter()
+    ///     .map(|e| e.attribute(&doc, "id").unwrap())
+    ///     .collect();
+    /// assert_eq!(ids, vec!["1", "2", "3"]);
+    /// // The comment is still the second child.
+    /// assert!(root.children(&doc)[1].as_element().is_none());
+    /// ```
+    pub fn sort_children_by<F, K>(&self, doc: &mut Document, key: F)
+    where
+        F: Fn(&Element, &Document) -> K,
+        K: Ord,
+    {
+        let mut positions = Vec::new();
+        let mut keyed = Vec::new();
+        for (i, node) in self.children(doc).iter().enumerate() {
+            if let Node::Element(elem) = node {
+                positions.push(i);
+                keyed.push((key(elem, doc), *elem));
+            }
+        }
+        keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let children = &mut self.mut_data(doc).children;
+        for (pos, (_, elem)) in positions.into_iter().zip(keyed) {
+            children[pos] = Node::Element(elem);
+        }
+    }
+
+    /// Reorder the child elements of this element by their name (without the namespace prefix).
+    ///
+    /// Shorthand for `self.sort_children_by(doc, |e, doc| e.name(doc).to_string())`.
+    /// See [`Element::sort_children_by`] for how non-element children are handled.
+    pub fn sort_children_by_name(&self, doc: &mut Document) {
+        self.sort_children_by(doc, |e, doc| e.name(doc).to_string())
+    }
+
     /// Removes itself from its parent. Note that you can't attach this element to other documents.
     ///
     /// # Errors
@@ -1042,22 +2831,60 @@ impl Element {
         if self.is_container() {
             return Err(Error::ContainerCannotMove);
         }
-        let data = self.mut_data(doc);
-        if let Some(parent) = data.parent {
-            let pos = parent
-                .children(doc)
-                .iter()
-                .position(|n| n.as_element() == Some(*self))
-                .unwrap();
-            parent.remove_child(doc, pos);
+        if let Some(parent) = self.parent(doc) {
+            parent.remove_child_element(doc, *self);
         }
         Ok(())
     }
 }
 
+/// See [`Element::display`].
+struct ElementDisplay<'a> {
+    element: Element,
+    doc: &'a Document,
+}
+
+impl std::fmt::Display for ElementDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let xml = self
+            .element
+            .outer_xml(self.doc)
+            .map_err(|_| std::fmt::Error)?;
+        f.write_str(&xml)
+    }
+}
+
+/// A prefix or a local name is a valid XML name without a `:`.
+fn is_valid_name_part(name: &str) -> bool {
+    !name.contains(':') && is_valid_xml_name(name)
+}
+
+/// A valid `name` or `prefix:name`.
+pub(crate) fn is_valid_qualified_name(name: &str) -> bool {
+    match name.split_once(':') {
+        Some((prefix, name)) => is_valid_name_part(prefix) && is_valid_name_part(name),
+        None => is_valid_name_part(name),
+    }
+}
+
+/// Check that the text of a comment or processing instruction `node` does not end
+/// it early (or, for a comment, contain `--`). Other nodes are always valid.
+pub(crate) fn check_node_text(node: &Node) -> Result<()> {
+    match node {
+        Node::Comment(text) if text.contains("--") || text.ends_with('-') => {
+            Err(Error::InvalidComment(text.clone()))
+        }
+        Node::PI(text) if text.contains("?>") => {
+            Err(Error::InvalidProcessingInstruction(text.clone()))
+        }
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Document, Element, Node};
+    use super::{Document, Element, Error, Node};
+    use std::sync::Arc;
 
     #[test]
     fn test_children() {
@@ -1109,7 +2936,7 @@ mod tests {
         let doc = Document::parse_str(xml).unwrap();
         let container = doc.container().children(&doc)[0].as_element().unwrap();
         let child_elements = container.child_elements(&doc);
-        let foo = *child_elements.get(0).unwrap();
+        let foo = *child_elements.first().unwrap();
         let bar = *child_elements.get(1).unwrap();
         let c = bar.child_elements(&doc)[0];
         assert_eq!(c.prefix_name(&doc), ("", "c"));
@@ -1127,6 +2954,67 @@ mod tests {
         assert_eq!(container.namespace(&doc).unwrap(), "ns");
     }
 
+    #[test]
+    fn test_set_namespace() {
+        let mut doc = Document::new();
+        let container = doc.container();
+        let root = Element::build("p:root").push_to(&mut doc, container);
+        let item = Element::build("item").push_to(&mut doc, root);
+        item.set_namespace(&mut doc, "a", "http://a").unwrap();
+        assert_eq!(item.namespace(&doc), Some("http://a"));
+        assert_eq!(
+            item.outer_xml(&doc).unwrap(),
+            r#"<a:item xmlns:a="http://a"/>"#
+        );
+
+        root.set_namespace(&mut doc, "", "http://default").unwrap();
+        assert_eq!(root.full_name(&doc), "root");
+        assert_eq!(root.namespace(&doc), Some("http://default"));
+        assert_eq!(
+            root.outer_xml(&doc).unwrap(),
+            "<root xmlns=\"http://default\">\n  <a:item xmlns:a=\"http://a\"/>\n</root>"
+        );
+
+        let result = item.set_namespace(&mut doc, "b:c", "http://b");
+        assert!(matches!(result, Err(Error::InvalidName(_))));
+        assert!(!item.namespace_decls(&doc).contains_key("b:c"));
+    }
+
+    #[test]
+    fn test_interned_names() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <root><item id="1"/><item id="2"/></root>"#;
+        let mut doc = Document::parse_str(xml).unwrap();
+        let items = doc.root_element().unwrap().child_elements(&doc);
+        let (a, b) = (items[0].data(&doc), items[1].data(&doc));
+        assert!(Arc::ptr_eq(&a.full_name, &b.full_name));
+        assert!(Arc::ptr_eq(&a.attribute_order[0], &b.attribute_order[0]));
+
+        // Renaming one element does not affect the other.
+        items[0].set_name(&mut doc, "entry").unwrap();
+        assert_eq!(items[0].full_name(&doc), "entry");
+        assert_eq!(items[1].full_name(&doc), "item");
+    }
+
+    #[test]
+    fn test_undeclared_prefix() {
+        let mut doc = Document::new();
+        let container = doc.container();
+        let root = Element::build("root")
+            .namespace_decl("a", "http://a")
+            .push_to(&mut doc, container);
+        let child = Element::build("a:child").push_to(&mut doc, root);
+        Element::build("b:child").push_to(&mut doc, child);
+
+        let result = child.collect_external_namespace_decls(&doc);
+        assert!(matches!(result, Err(Error::UndeclaredPrefix(prefix)) if prefix == "b"));
+        assert!(matches!(
+            child.outer_xml(&doc),
+            Err(Error::UndeclaredPrefix(_))
+        ));
+        assert_eq!(root.undeclared_prefixes(&doc), vec!["b".to_string()]);
+    }
+
     #[test]
     fn test_find_text_content() {
         let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -1200,9 +3088,106 @@ mod tests {
         assert_eq!(root.children(&doc)[0].as_element().unwrap(), a);
         assert_eq!(a.parent(&doc).unwrap(), root);
 
+        // Element.swap_children
+        let b = Element::new(&mut doc, "b");
+        root.push_child(&mut doc, Node::Element(b)).unwrap();
+        root.swap_children(&mut doc, 0, 1);
+        assert_eq!(root.child_elements(&doc), vec![b, a]);
+        assert_eq!(a.parent(&doc).unwrap(), root);
+        assert_eq!(b.parent(&doc).unwrap(), root);
+        root.remove_child(&mut doc, 0);
+
         // Element.detatch
         a.detatch(&mut doc).unwrap();
         assert_eq!(root.children(&doc).len(), 0);
         assert_eq!(a.parent(&doc), None);
+
+        // Element.replace_child
+        root.push_child(&mut doc, Node::Element(a)).unwrap();
+        let old = root
+            .replace_child(&mut doc, 0, Node::Text("text".to_string()))
+            .unwrap();
+        assert_eq!(old.as_element(), Some(a));
+        assert_eq!(a.parent(&doc), None);
+        assert_eq!(root.children(&doc)[0].as_text(), Some("text"));
+
+        // Element.replace_with
+        let c = Element::build("c").push_to(&mut doc, root);
+        assert!(matches!(
+            a.replace_with(&mut doc, c),
+            Err(Error::HasNoParent)
+        ));
+        assert!(matches!(
+            c.replace_with(&mut doc, root),
+            Err(Error::HasAParent)
+        ));
+        assert!(matches!(
+            c.replace_with(&mut doc, container),
+            Err(Error::ContainerCannotMove)
+        ));
+        c.replace_with(&mut doc, a).unwrap();
+        assert_eq!(root.children(&doc)[1].as_element(), Some(a));
+        assert_eq!(a.parent(&doc).unwrap(), root);
+        assert_eq!(c.parent(&doc), None);
+
+        // Element.insert_before, Element.insert_after, Element.prepend_child
+        let d = Element::new(&mut doc, "d");
+        assert!(matches!(
+            root.insert_before(&mut doc, c, d.as_node()),
+            Err(Error::NotAChild)
+        ));
+        assert!(matches!(
+            root.insert_after(&mut doc, c, d.as_node()),
+            Err(Error::NotAChild)
+        ));
+        assert_eq!(d.parent(&doc), None);
+        root.insert_after(&mut doc, a, d.as_node()).unwrap();
+        root.prepend_child(&mut doc, c.as_node()).unwrap();
+        let children = root.child_elements(&doc);
+        assert_eq!(children[0], c);
+        assert_eq!(children[children.len() - 1], d);
+        assert_eq!(children[children.len() - 2], a);
+
+        // Element.swap_with
+        let e = Element::build("e").push_to(&mut doc, d);
+        assert!(matches!(d.swap_with(&mut doc, e), Err(Error::CyclicMove)));
+        assert!(matches!(
+            c.swap_with(&mut doc, container),
+            Err(Error::ContainerCannotMove)
+        ));
+        c.swap_with(&mut doc, d).unwrap();
+        assert_eq!(root.child_elements(&doc)[0], d);
+        assert_eq!(root.child_elements(&doc).last(), Some(&c));
+        c.swap_with(&mut doc, e).unwrap();
+        assert_eq!(d.child_elements(&doc), vec![c]);
+        assert_eq!(c.parent(&doc), Some(d));
+        assert_eq!(e.parent(&doc), Some(root));
+        assert_eq!(root.child_elements(&doc).last(), Some(&e));
+    }
+
+    #[test]
+    fn test_set_inner_xml() {
+        let mut doc = Document::new();
+        let root = Element::new(&mut doc, "root");
+        doc.push_root_node(root.as_node()).unwrap();
+        let old = Element::build("old").push_to(&mut doc, root);
+
+        root.set_inner_xml(&mut doc, "<a x=\"1\"><b/></a> text <c/>")
+            .unwrap();
+        assert_eq!(old.parent(&doc), None);
+        let children = root.child_elements(&doc);
+        assert_eq!(children.len(), 2);
+        let a = children[0];
+        assert_eq!(a.name(&doc), "a");
+        assert_eq!(a.attribute(&doc, "x"), Some("1"));
+        assert_eq!(a.parent(&doc), Some(root));
+        let b = a.child_elements(&doc)[0];
+        assert_eq!(b.parent(&doc), Some(a));
+        assert_eq!(root.children(&doc)[1].text_content(&doc), " text ");
+
+        // Malformed xml is rejected and the element is left unchanged.
+        let result = root.set_inner_xml(&mut doc, "<a><b></a>");
+        assert!(matches!(result, Err(Error::MalformedXML(_))));
+        assert_eq!(root.child_elements(&doc), children);
     }
 }
@@ -1,3 +1,4 @@
+use quick_xml::escape::EscapeError;
 use quick_xml::events::attributes::AttrError;
 use quick_xml::Error as XMLError;
 use std::sync::Arc;
@@ -23,6 +24,40 @@ pub enum Error {
     ContainerCannotMove,
     /// You need to call `element.detatch()` before assigning another parent.
     HasAParent,
+    /// The operation requires an element with a parent, but the element has none.
+    HasNoParent,
+    /// The given reference element is not a child of the element.
+    NotAChild,
+    /// The element cannot be moved into its own sub-tree.
+    CyclicMove,
+    /// The given namespace url has no prefix declared in this context.
+    UndeclaredNamespace(String),
+    /// The XML version is not supported. Only `1.0` and `1.1` are valid versions.
+    InvalidVersion(String),
+    /// Reference to an entity which is not predefined and is not
+    /// in [`crate::ReadOptions::entities`].
+    UnknownEntity(String),
+    /// Expanding an internal entity nests too deeply or produces too much text.
+    EntityExpansionLimit,
+    /// The document has more than one root element,
+    /// see [`crate::ReadOptions::require_single_root`].
+    MultipleRootElements,
+    /// The document has no root element, see [`crate::Document::validate_well_formed`].
+    NoRootElement,
+    /// An element has more than one attribute with the same name.
+    DuplicateAttribute { element: String, attribute: String },
+    /// An element has more attributes than allowed by
+    /// [`crate::ReadOptions::max_attributes_per_element`].
+    TooManyAttributes { element: String, limit: usize },
+    /// The given string is not a valid XML name. See [`crate::is_valid_xml_name`].
+    InvalidName(String),
+    /// The given namespace prefix is used, but not declared in this context.
+    UndeclaredPrefix(String),
+    /// The content of a [`crate::Node::Comment`] cannot be written, because it contains `--`
+    /// or ends with `-`.
+    InvalidComment(String),
+    /// The content of a [`crate::Node::PI`] cannot be written, because it contains `?>`.
+    InvalidProcessingInstruction(String),
 }
 
 impl std::fmt::Display for Error {
@@ -36,6 +71,33 @@ impl std::fmt::Display for Error {
                 f,
                 "Element already has a parent. Call detatch() before changing parent."
             ),
+            Error::HasNoParent => write!(f, "Element has no parent"),
+            Error::NotAChild => write!(f, "Element is not a child of this element"),
+            Error::CyclicMove => write!(f, "Element cannot be moved into its own sub-tree"),
+            Error::UndeclaredNamespace(url) => write!(f, "Namespace `{}` is not declared", url),
+            Error::InvalidVersion(version) => write!(f, "Invalid XML version `{}`", version),
+            Error::UnknownEntity(name) => write!(f, "Unknown entity `&{};`", name),
+            Error::EntityExpansionLimit => write!(f, "Entity expansion limit exceeded"),
+            Error::InvalidName(name) => write!(f, "`{}` is not a valid XML name", name),
+            Error::UndeclaredPrefix(prefix) => {
+                write!(f, "Namespace prefix `{}` is not declared", prefix)
+            }
+            Error::MultipleRootElements => write!(f, "Document has more than one root element"),
+            Error::NoRootElement => write!(f, "Document has no root element"),
+            Error::InvalidComment(text) => write!(f, "Invalid comment `{}`", text),
+            Error::InvalidProcessingInstruction(text) => {
+                write!(f, "Invalid processing instruction `{}`", text)
+            }
+            Error::DuplicateAttribute { element, attribute } => write!(
+                f,
+                "Element `{}` has duplicate attribute `{}`",
+                element, attribute
+            ),
+            Error::TooManyAttributes { element, limit } => write!(
+                f,
+                "Element `{}` has more than {} attributes",
+                element, limit
+            ),
         }
     }
 }
@@ -49,6 +111,15 @@ impl std::error::Error for Error {
     }
 }
 
+impl From<EscapeError> for Error {
+    fn from(err: EscapeError) -> Error {
+        match err {
+            EscapeError::UnrecognizedSymbol(_, name) => Error::UnknownEntity(name),
+            err => Error::MalformedXML(err.to_string()),
+        }
+    }
+}
+
 impl From<XMLError> for Error {
     fn from(err: XMLError) -> Error {
         match err {
@@ -58,6 +129,7 @@ impl From<XMLError> for Error {
             )),
             XMLError::Io(err) => Error::Io(err),
             XMLError::NonDecodable(_) => Error::CannotDecode,
+            XMLError::EscapeError(err) => err.into(),
             err => Error::MalformedXML(err.to_string()),
         }
     }
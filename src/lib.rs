@@ -61,12 +61,23 @@
 //!     .expect("Writing failed.");
 //! ```
 //!
+mod c14n;
+mod diff;
+mod doctype;
 mod document;
 mod element;
 mod error;
 mod parser;
+mod value;
+mod visitor;
 
-pub use crate::document::{Document, Node, WriteOptions};
+pub use crate::diff::DocDiff;
+pub use crate::doctype::{parse_doctype, DocTypeInfo, EntityDecl};
+pub use crate::document::{
+    AttributeOrder, Document, MemoryReport, Node, TextEscaping, WriteOptions,
+};
 pub use crate::element::{Element, ElementBuilder};
 pub use crate::error::{Error, Result};
-pub use crate::parser::{normalize_space, ReadOptions};
+pub use crate::parser::{is_valid_xml_name, normalize_space, ReadOptions, XmlEvent, XmlEvents};
+pub use crate::value::XmlValue;
+pub use crate::visitor::Visitor;
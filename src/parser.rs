@@ -1,13 +1,16 @@
+use crate::doctype::parse_doctype;
 use crate::document::{Document, Node};
 use crate::element::Element;
 use crate::error::{Error, Result};
 use encoding_rs::Decoder;
 use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
-use quick_xml::events::{BytesDecl, BytesStart, Event};
+use quick_xml::escape::{unescape, EscapeError};
+use quick_xml::events::{BytesDecl, BytesStart, BytesText, Event};
 use quick_xml::Reader;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{BufRead, Read};
+use std::sync::Arc;
 
 pub(crate) struct DecodeReader<R: Read> {
     decoder: Option<Decoder>,
@@ -120,9 +123,11 @@ pub struct ReadOptions {
     /// Default: `true`
     pub empty_text_node: bool,
     /// Trims leading and ending whitespaces in `Node::Text`, and ignore node if it is empty.
+    /// Text inside elements with `xml:space="preserve"` is never trimmed.
     /// Default: `true`
     pub trim_text: bool,
     /// Ignore Node::Text that only has whitespaces.
+    /// Text inside elements with `xml:space="preserve"` is never ignored.
     /// Only makes sense if `trim_text` is `false`. (If both are `true`, performance takes a hit for no gain)
     /// Default: `false`
     pub ignore_whitespace_only: bool,
@@ -146,6 +151,74 @@ pub struct ReadOptions {
     ///
     /// Default: `false`
     pub enforce_encoding: bool,
+    /// Replacement text of custom entities, e.g. `"product"` for `&product;`, used in text
+    /// and attribute values. The replacement text is inserted as is, it is not parsed as markup.
+    ///
+    /// Referencing an entity which is neither predefined nor in this map is an
+    /// [Error::UnknownEntity].
+    ///
+    /// Default: empty
+    pub entities: HashMap<String, String>,
+    /// Expand references to internal entities declared in the document type declaration,
+    /// e.g. `<!ENTITY product "Widget">`. Unlike [`ReadOptions::entities`], the replacement
+    /// text can reference other entities, which are expanded recursively. If an entity is
+    /// also in [`ReadOptions::entities`], the value from `entities` is used.
+    ///
+    /// Like for [`ReadOptions::entities`], the expanded replacement text is inserted as
+    /// character data: markup in it (e.g. `<!ENTITY b "<b>bold</b>">`) is not parsed
+    /// into elements, but kept as text.
+    ///
+    /// Entities which nest too deeply, or references which expand to too much text
+    /// in total, are an [Error::EntityExpansionLimit].
+    ///
+    /// Default: `false`
+    pub expand_internal_entities: bool,
+    /// Returns [Error::DuplicateAttribute] if an element has two attributes (or namespace
+    /// declarations) with the same name, as required by the XML specification.
+    /// If set to `false`, the last value of the attribute is used.
+    ///
+    /// Default: `false`
+    pub error_on_duplicate_attribute: bool,
+    /// Expected number of elements in the document, used to pre-allocate the element storage
+    /// (see [`Document::with_capacity`]). If `None`, the number is estimated from the length
+    /// of the input when parsing a string or a file, and no memory is pre-allocated
+    /// when parsing a reader.
+    ///
+    /// This mostly reduces allocator churn, the effect on parse time is small: parsing
+    /// `benches/large.xml` (25MB, ~480k elements) from a string takes around 255-260ms both
+    /// with the estimate and with the exact count, compared to 260-265ms without
+    /// pre-allocation (best of 10 runs each, release build).
+    ///
+    /// Default: `None`
+    pub expected_elements: Option<usize>,
+    /// Trims `Node::Text` and replaces every sequence of whitespace inside it with a single
+    /// space (see [`normalize_space`]). Text which becomes empty is ignored. `Node::CData`
+    /// and text inside elements with `xml:space="preserve"` is never modified.
+    /// Character references (e.g. `&#10;`) are not collapsed.
+    /// Default: `false`
+    pub collapse_whitespace: bool,
+    /// Keep all text, including whitespace, exactly as in the document, so that writing
+    /// the document with [`crate::WriteOptions::preserve_formatting`] reproduces its layout.
+    /// If set, `trim_text`, `ignore_whitespace_only` and `collapse_whitespace` are ignored,
+    /// the content of the document type declaration (`Node::DocType`) is not unescaped, and
+    /// elements written as `<tag></tag>` are marked (see [`Element::is_force_expanded`]).
+    /// Default: `false`
+    pub preserve_formatting: bool,
+    /// Store text as [`Node::RawText`] with the escaped content of the document, e.g. `&amp;`
+    /// instead of `&`, so that it is written back verbatim. The content still needs
+    /// to be well-formed: references to unknown entities are an error.
+    /// [`Element::text_content`] and similar methods still unescape predefined entities and
+    /// character references, but not custom entities (see [`ReadOptions::entities`]).
+    /// Default: `false`
+    pub keep_raw_text: bool,
+    /// Returns [Error::MultipleRootElements] if the document has more than one root element,
+    /// as required by the XML specification. Other root nodes (e.g. comments) are allowed.
+    /// Default: `false`
+    pub require_single_root: bool,
+    /// Returns [Error::TooManyAttributes] if an element has more attributes (including
+    /// namespace declarations) than this, e.g. to limit the memory used by untrusted input.
+    /// Default: `None`
+    pub max_attributes_per_element: Option<usize>,
 }
 
 impl Default for ReadOptions {
@@ -157,237 +230,155 @@ impl Default for ReadOptions {
             require_decl: true,
             encoding: None,
             enforce_encoding: false,
+            entities: HashMap::new(),
+            expand_internal_entities: false,
+            error_on_duplicate_attribute: false,
+            expected_elements: None,
+            collapse_whitespace: false,
+            preserve_formatting: false,
+            keep_raw_text: false,
+            require_single_root: false,
+            max_attributes_per_element: None,
         }
     }
 }
 
-//TODO: don't unwrap element_stack.last() or pop(). Invalid XML file can crash the software.
-pub(crate) struct DocumentParser {
-    doc: Document,
-    read_opts: ReadOptions,
-    encoding: Option<&'static Encoding>,
-    element_stack: Vec<Element>,
-}
-
-impl DocumentParser {
-    pub(crate) fn parse_reader<R: Read>(reader: R, opts: ReadOptions) -> Result<Document> {
-        let doc = Document::new();
-        let element_stack = vec![doc.container()];
-        let mut parser = DocumentParser {
-            doc,
-            read_opts: opts,
-            encoding: None,
-            element_stack,
-        };
-        parser.parse_start(reader)?;
-        Ok(parser.doc)
+impl ReadOptions {
+    /// Apply the overrides of [`ReadOptions::preserve_formatting`].
+    fn effective(mut self) -> ReadOptions {
+        if self.preserve_formatting {
+            self.trim_text = false;
+            self.ignore_whitespace_only = false;
+            self.collapse_whitespace = false;
+        }
+        self
     }
+}
 
-    fn handle_decl(&mut self, ev: &BytesDecl) -> Result<()> {
-        self.doc.version = String::from_utf8(ev.version()?.to_vec())?;
-        self.encoding = match ev.encoding() {
-            Some(res) => {
-                let encoding = Encoding::for_label(&res?).ok_or(Error::CannotDecode)?;
-                if encoding == UTF_8 {
-                    None
-                } else {
-                    Some(encoding)
-                }
-            }
-            None => None,
-        };
-        self.doc.standalone = match ev.standalone() {
-            Some(res) => {
-                let val = std::str::from_utf8(&res?)?.to_lowercase();
-                match val.as_str() {
-                    "yes" => true,
-                    "no" => false,
-                    _ => {
-                        return Err(Error::MalformedXML(
-                            "Standalone Document Declaration has non boolean value".to_string(),
-                        ))
-                    }
-                }
-            }
-            None => false,
-        };
-        Ok(())
-    }
+/// An event produced by [`XmlEvents`] while reading an XML document.
+///
+/// The events follow the same conventions as [`Document`]: attribute values are normalized
+/// and unescaped, namespace declarations are separated from the other attributes, and
+/// text is unescaped and trimmed according to [`ReadOptions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlEvent {
+    /// The XML declaration. If present, this is always the first event.
+    Declaration {
+        version: String,
+        encoding: Option<String>,
+        standalone: Option<bool>,
+    },
+    /// Start of an element. Every `ElementStart` is eventually followed by a matching
+    /// `ElementEnd`, even if the element was written as a self-closing tag (`<tag />`).
+    ElementStart {
+        /// Full name of the element, including its namespace prefix.
+        name: String,
+        /// Attributes of the element, excluding namespace declarations.
+        attributes: HashMap<String, String>,
+        /// Full names of `attributes` in the order in which they appear in the document.
+        attribute_order: Vec<String>,
+        /// Namespace declarations (`xmlns:prefix="url"`) of the element.
+        /// Default namespace has empty string as key.
+        namespace_decls: HashMap<String, String>,
+        /// `true` if the element was written as a self-closing tag (`<tag />`).
+        self_closing: bool,
+    },
+    /// End of an element.
+    ElementEnd {
+        /// Full name of the element, including its namespace prefix.
+        name: String,
+    },
+    /// Character data. See [`Node::Text`].
+    Text(String),
+    /// Character data which is not unescaped. Only produced if
+    /// [`ReadOptions::keep_raw_text`] is set, instead of [`XmlEvent::Text`].
+    RawText(String),
+    /// See [`Node::Comment`].
+    Comment(String),
+    /// See [`Node::CData`].
+    CData(String),
+    /// See [`Node::PI`].
+    PI(String),
+    /// See [`Node::DocType`].
+    DocType(String),
+}
 
-    fn create_element(&mut self, parent: Element, ev: &BytesStart) -> Result<Element> {
-        let full_name = String::from_utf8(ev.name().into_inner().to_vec())?;
-        let mut namespace_decls = HashMap::new();
-        let mut attributes = HashMap::new();
-        for attr in ev.attributes() {
-            let mut attr = attr?;
-            attr.value = Cow::Owned(normalize_space(&attr.value));
-            let key = String::from_utf8(attr.key.into_inner().to_vec())?;
-            let value = String::from_utf8(attr.unescape_value()?.as_bytes().to_vec())?;
-            if key == "xmlns" {
-                namespace_decls.insert(String::new(), value);
-                continue;
-            } else if let Some(prefix) = key.strip_prefix("xmlns:") {
-                namespace_decls.insert(prefix.to_owned(), value);
-                continue;
-            }
-            attributes.insert(key, value);
-        }
-        let elem = Element::with_data(&mut self.doc, full_name, attributes, namespace_decls);
-        parent
-            .push_child(&mut self.doc, Node::Element(elem))
-            .unwrap();
-        Ok(elem)
-    }
+/// A streaming (pull) parser which reads XML events without building a [`Document`] tree.
+///
+/// Create it using [`Document::event_reader`]. This is useful for documents which are too large
+/// to fit into memory. It is an iterator over `Result<XmlEvent>`. After an error is returned,
+/// the iterator does not produce any more events.
+///
+/// # Examples
+///
+/// ```rust
+/// use biodivine_xml_doc::{Document, ReadOptions, XmlEvent};
+///
+/// let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+/// <list>
+///     <item>a</item>
+///     <item />
+/// </list>
+/// "#;
+///
+/// let events = Document::event_reader(xml.as_bytes(), ReadOptions::default()).unwrap();
+/// let mut items = 0;
+/// for event in events {
+///     if let XmlEvent::ElementStart { name, .. } = event.unwrap() {
+///         if name == "item" {
+///             items += 1;
+///         }
+///     }
+/// }
+/// assert_eq!(items, 2);
+/// ```
+pub struct XmlEvents<R: Read> {
+    reader: Reader<DecodeReader<R>>,
+    read_opts: ReadOptions,
+    buf: Vec<u8>,
+    pending: VecDeque<XmlEvent>,
+    depth: usize,
+    // `xml:space="preserve"` state of open elements.
+    space_preserve: Vec<bool>,
+    internal_entities: InternalEntities,
+    // Bytes consumed before `reader` was created, see `XmlEvents::buffer_position`.
+    position_offset: usize,
+    // Encoding given by the byte order mark at the start of the input.
+    bom: Option<&'static Encoding>,
+    done: bool,
+}
 
-    // Returns true if document parsing is finished.
-    fn handle_event(&mut self, event: Event) -> Result<bool> {
-        match event {
-            Event::Start(ref ev) => {
-                let parent = *self
-                    .element_stack
-                    .last()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
-                let element = self.create_element(parent, ev)?;
-                self.element_stack.push(element);
-                Ok(false)
-            }
-            Event::End(_) => {
-                let elem = self
-                    .element_stack
-                    .pop()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?; // quick-xml checks if tag names match for us
-                if self.read_opts.empty_text_node {
-                    // distinguish <tag></tag> and <tag />
-                    if !elem.has_children(&self.doc) {
-                        elem.push_child(&mut self.doc, Node::Text(String::new()))
-                            .unwrap();
-                    }
-                }
-                Ok(false)
-            }
-            Event::Empty(ref ev) => {
-                let parent = *self
-                    .element_stack
-                    .last()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
-                self.create_element(parent, ev)?;
-                Ok(false)
-            }
-            // Comment, CData, and PI content should not be escaped,
-            // but quick-xml assumes only CDATA is not escaped.
-            Event::Text(ev) => {
-                if self.read_opts.ignore_whitespace_only && only_has_whitespace(&ev) {
-                    return Ok(false);
-                }
-                // when trim_text, ignore_whitespace_only, empty_text_node are all false
-                if ev.is_empty() {
-                    return Ok(false);
-                }
-                let content = ev.unescape()?.to_string();
-                let node = Node::Text(content);
-                let parent = *self
-                    .element_stack
-                    .last()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
-            }
-            Event::DocType(ev) => {
-                // Event::DocType comes with one leading whitespace. Strip the whitespace.
-                let content = ev.unescape()?;
-                let raw = content.as_bytes();
-                let content = if !raw.is_empty() && raw[0] == b' ' {
-                    String::from_utf8(raw[1..].to_vec())?
-                } else {
-                    String::from_utf8(raw.to_vec())?
-                };
-                let node = Node::DocType(content);
-                let parent = *self
-                    .element_stack
-                    .last()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
-            }
-            Event::Comment(ev) => {
-                let content = String::from_utf8(ev.to_vec())?;
-                let node = Node::Comment(content);
-                let parent = *self
-                    .element_stack
-                    .last()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
-            }
-            Event::CData(ev) => {
-                let content = String::from_utf8(ev.to_vec())?;
-                let node = Node::CData(content);
-                let parent = *self
-                    .element_stack
-                    .last()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
-            }
-            Event::PI(ev) => {
-                let content = String::from_utf8(ev.to_vec())?;
-                let node = Node::PI(content);
-                let parent = *self
-                    .element_stack
-                    .last()
-                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
-                parent.push_child(&mut self.doc, node).unwrap();
-                Ok(false)
-            }
-            Event::Decl(_) => Err(Error::MalformedXML(
-                "XML declaration found in the middle of the document".to_string(),
-            )),
-            Event::Eof => Ok(true),
-        }
+impl<R: Read> XmlEvents<R> {
+    pub(crate) fn new(reader: R, opts: ReadOptions) -> Result<XmlEvents<R>> {
+        XmlEvents::with_decoding(reader, opts, true)
     }
 
-    // Sniff encoding and consume BOM
-    fn sniff_encoding<R: Read>(
-        &mut self,
-        decodereader: &mut DecodeReader<R>,
-    ) -> Result<Option<&'static Encoding>> {
-        let bytes = decodereader.fill_buf()?;
-        let encoding = match bytes {
-            [0x3c, 0x3f, ..] => None, // UTF-8 '<?'
-            [0xfe, 0xff, ..] => {
-                // UTF-16 BE BOM
-                decodereader.consume(2);
-                Some(UTF_16BE)
-            }
-            [0xff, 0xfe, ..] => {
-                // UTF-16 LE BOM
-                decodereader.consume(2);
-                Some(UTF_16LE)
-            }
-            [0xef, 0xbb, 0xbf, ..] => {
-                // UTF-8 BOM
-                decodereader.consume(3);
-                None
-            }
-            [0x00, 0x3c, 0x00, 0x3f, ..] => Some(UTF_16BE),
-            [0x3c, 0x00, 0x3f, 0x00, ..] => Some(UTF_16LE),
-            _ => None, // Try decoding it with UTF-8
-        };
-        Ok(encoding)
+    /// Read events from a reader which is known to be UTF-8, e.g. from a `&str`.
+    /// The encoding in the document declaration is still checked, but not used for decoding.
+    pub(crate) fn new_utf8(reader: R, opts: ReadOptions) -> Result<XmlEvents<R>> {
+        XmlEvents::with_decoding(reader, opts, false)
     }
 
-    // Look at the document decl and figure out the document encoding
-    fn parse_start<R: Read>(&mut self, reader: R) -> Result<()> {
+    /// Look at the document decl and figure out the document encoding.
+    ///
+    /// If `detect_encoding` is false, the input is always decoded as UTF-8.
+    fn with_decoding(reader: R, opts: ReadOptions, detect_encoding: bool) -> Result<XmlEvents<R>> {
+        let opts = opts.effective();
         let mut decodereader = DecodeReader::new(reader, None);
-        let mut init_encoding = self.sniff_encoding(&mut decodereader)?;
-        let requested_encoding = self
-            .read_opts
+        let (mut init_encoding, bom) = sniff_encoding(&mut decodereader)?;
+        let mut position_offset = match bom {
+            Some(bom) if bom == UTF_8 => 3,
+            Some(_) => 2,
+            None => 0,
+        };
+        let requested_encoding = opts
             .encoding
             .as_ref()
             .map(|enc| Encoding::for_label(enc.as_bytes()).ok_or(Error::CannotDecode))
             .transpose()?;
 
-        if requested_encoding.is_some() {
+        // A byte order mark determines the encoding, unless a specific encoding is enforced.
+        if requested_encoding.is_some() && (bom.is_none() || opts.enforce_encoding) {
             // If UTF is requested, the correct value to use is `None`, because UTF is the default.
             if requested_encoding == Some(UTF_8) {
                 init_encoding = None;
@@ -395,20 +386,24 @@ impl DocumentParser {
                 init_encoding = requested_encoding;
             }
         }
+        if !detect_encoding {
+            init_encoding = None;
+        }
 
         decodereader.set_encoding(init_encoding);
         let mut xmlreader = Reader::from_reader(decodereader);
-        xmlreader.trim_text(self.read_opts.trim_text);
+        xmlreader.trim_text(opts.trim_text);
 
         let mut buf = Vec::with_capacity(200);
+        let mut pending = VecDeque::new();
 
         // Skip first event if it only has whitespace
         let event = match xmlreader.read_event_into(&mut buf)? {
             Event::Text(ev) => {
                 let should_ignore = {
-                    let is_empty = ev.len() == 0;
+                    let is_empty = ev.is_empty();
                     let is_whitespace = only_has_whitespace(&ev);
-                    is_empty || (self.read_opts.ignore_whitespace_only && is_whitespace)
+                    is_empty || (opts.ignore_whitespace_only && is_whitespace)
                 };
                 if should_ignore {
                     xmlreader.read_event_into(&mut buf)?
@@ -419,58 +414,662 @@ impl DocumentParser {
             ev => ev,
         };
 
-        if let Event::Decl(ev) = event {
-            self.handle_decl(&ev)?;
-            if self.read_opts.enforce_encoding {
+        let mut events = if let Event::Decl(ev) = event {
+            let (declaration, encoding) = read_decl(&ev)?;
+            if opts.enforce_encoding {
                 // User requested encoding X, but Y was actually found in the document declaration.
-                // Note that if the declaration contains UTF-8, then self.encoding is actually
+                // Note that if the declaration contains UTF-8, then `encoding` is actually
                 // `None`, so we have to account for that.
                 if requested_encoding.is_none() {
                     return Err(Error::CannotDecode);
                 }
                 if requested_encoding == Some(UTF_8) {
-                    if self.encoding.is_some() {
+                    if encoding.is_some() {
                         return Err(Error::CannotDecode);
                     }
-                } else if self.encoding != requested_encoding {
+                } else if encoding != requested_encoding {
                     return Err(Error::CannotDecode);
                 }
             }
             // Encoding::for_label("UTF-16") defaults to UTF-16 LE, even though it could be UTF-16 BE
-            if self.encoding != init_encoding
-                && !(self.encoding == Some(UTF_16LE) && init_encoding == Some(UTF_16BE))
+            if detect_encoding
+                && (bom.is_none() || opts.enforce_encoding)
+                && encoding != init_encoding
+                && !(encoding == Some(UTF_16LE) && init_encoding == Some(UTF_16BE))
             {
+                position_offset += xmlreader.buffer_position();
                 let mut decode_reader = xmlreader.into_inner();
-                decode_reader.set_encoding(self.encoding);
+                decode_reader.set_encoding(encoding);
                 xmlreader = Reader::from_reader(decode_reader);
-                xmlreader.trim_text(self.read_opts.trim_text);
+                xmlreader.trim_text(opts.trim_text);
             }
-        } else if self.read_opts.require_decl {
+            pending.push_back(declaration);
+            XmlEvents {
+                reader: xmlreader,
+                read_opts: opts,
+                buf,
+                pending,
+                depth: 0,
+                space_preserve: Vec::new(),
+                internal_entities: InternalEntities::default(),
+                position_offset,
+                bom,
+                done: false,
+            }
+        } else if opts.require_decl {
             return Err(Error::MalformedXML(
                 "Didn't find XML Declaration at the start of file".to_string(),
             ));
-        } else if self.handle_event(event)? {
+        } else {
+            let event = event.into_owned();
+            let mut events = XmlEvents {
+                reader: xmlreader,
+                read_opts: opts,
+                buf,
+                pending,
+                depth: 0,
+                space_preserve: Vec::new(),
+                internal_entities: InternalEntities::default(),
+                position_offset,
+                bom,
+                done: false,
+            };
+            events.handle_event(event)?;
+            events
+        };
+        events.buf.clear();
+        Ok(events)
+    }
+
+    /// Convert a `quick_xml` event into [`XmlEvent`]s and put them into the `pending` queue.
+    ///
+    /// Returns `true` if document parsing is finished.
+    fn handle_event(&mut self, event: Event) -> Result<bool> {
+        let event = match event {
+            Event::Start(ref ev) => {
+                self.depth += 1;
+                let start = read_start(ev, false, &self.read_opts, &mut self.internal_entities)?;
+                if let XmlEvent::ElementStart { attributes, .. } = &start {
+                    let inherited = self.space_preserve.last().copied().unwrap_or(false);
+                    let preserve = match attributes.get("xml:space").map(|s| s.as_str()) {
+                        Some("preserve") => true,
+                        Some("default") => false,
+                        _ => inherited,
+                    };
+                    self.space_preserve.push(preserve);
+                    self.update_trim_text();
+                }
+                start
+            }
+            Event::Empty(ref ev) => {
+                let start = read_start(ev, true, &self.read_opts, &mut self.internal_entities)?;
+                let name = String::from_utf8(ev.name().into_inner().to_vec())?;
+                self.pending.push_back(start);
+                XmlEvent::ElementEnd { name }
+            }
+            Event::End(ev) => {
+                // quick-xml checks if tag names match for us
+                if self.depth == 0 {
+                    return Err(Error::MalformedXML("Malformed Element Tree".to_string()));
+                }
+                self.depth -= 1;
+                self.space_preserve.pop();
+                self.update_trim_text();
+                let name = String::from_utf8(ev.name().into_inner().to_vec())?;
+                XmlEvent::ElementEnd { name }
+            }
+            // Comment, CData, and PI content should not be escaped,
+            // but quick-xml assumes only CDATA is not escaped.
+            Event::Text(ev) => {
+                let preserve = self.space_preserve.last().copied().unwrap_or(false);
+                if self.read_opts.ignore_whitespace_only && !preserve && only_has_whitespace(&ev) {
+                    return Ok(false);
+                }
+                // when trim_text, ignore_whitespace_only, empty_text_node are all false
+                if ev.is_empty() {
+                    return Ok(false);
+                }
+                let ev = if self.read_opts.collapse_whitespace && !preserve {
+                    let collapsed = normalize_space(&ev);
+                    if collapsed.is_empty() {
+                        return Ok(false);
+                    }
+                    BytesText::from_escaped(String::from_utf8(collapsed)?)
+                } else {
+                    ev
+                };
+                let entities = &self.read_opts.entities;
+                self.internal_entities.expand_references(&ev, entities)?;
+                let internal = &self.internal_entities;
+                let text = ev.unescape_with(|name| internal.resolve(entities, name))?;
+                if self.read_opts.keep_raw_text {
+                    XmlEvent::RawText(String::from_utf8(ev.to_vec())?)
+                } else {
+                    XmlEvent::Text(text.into_owned())
+                }
+            }
+            Event::DocType(ev) => {
+                if self.read_opts.expand_internal_entities {
+                    // Entity values must be read before unescaping.
+                    self.internal_entities.declare(std::str::from_utf8(&ev)?);
+                }
+                // Event::DocType comes with one leading whitespace. Strip the whitespace.
+                let content = if self.read_opts.preserve_formatting {
+                    // The internal subset is markup, so it is kept as is.
+                    Cow::Borrowed(std::str::from_utf8(&ev)?)
+                } else {
+                    match ev.unescape() {
+                        Ok(content) => content,
+                        // Entity values can reference entities declared by the doctype itself.
+                        // Such references cannot be unescaped, so the content is kept as is.
+                        Err(quick_xml::Error::EscapeError(EscapeError::UnrecognizedSymbol(..))) => {
+                            Cow::Borrowed(std::str::from_utf8(&ev)?)
+                        }
+                        Err(err) => return Err(err.into()),
+                    }
+                };
+                let raw = content.as_bytes();
+                let content = if !raw.is_empty() && raw[0] == b' ' {
+                    String::from_utf8(raw[1..].to_vec())?
+                } else {
+                    String::from_utf8(raw.to_vec())?
+                };
+                XmlEvent::DocType(content)
+            }
+            Event::Comment(ev) => XmlEvent::Comment(String::from_utf8(ev.to_vec())?),
+            Event::CData(ev) => XmlEvent::CData(String::from_utf8(ev.to_vec())?),
+            Event::PI(ev) => XmlEvent::PI(String::from_utf8(ev.to_vec())?),
+            Event::Decl(_) => {
+                return Err(Error::MalformedXML(
+                    "XML declaration found in the middle of the document".to_string(),
+                ))
+            }
+            Event::Eof => {
+                return if self.depth == 0 {
+                    Ok(true)
+                } else {
+                    Err(Error::MalformedXML("Closing tag not found.".to_string()))
+                };
+            }
+        };
+        self.pending.push_back(event);
+        Ok(false)
+    }
+
+    /// Text is never trimmed inside elements with `xml:space="preserve"`.
+    fn update_trim_text(&mut self) {
+        let preserve = self.space_preserve.last().copied().unwrap_or(false);
+        self.reader.trim_text(self.read_opts.trim_text && !preserve);
+    }
+
+    /// Read events until there is a pending event. Returns `false` if the document has ended.
+    /// Number of bytes of the input consumed by the parser so far, including the byte
+    /// order mark. Input which is not UTF-8 is counted after decoding it to UTF-8,
+    /// except for the byte order mark and the XML declaration.
+    pub(crate) fn buffer_position(&self) -> usize {
+        self.position_offset + self.reader.buffer_position()
+    }
+
+    fn fill_pending(&mut self) -> Result<bool> {
+        while self.pending.is_empty() {
+            let event = self.reader.read_event_into(&mut self.buf)?.into_owned();
+            self.buf.clear();
+            if self.handle_event(event)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for XmlEvents<R> {
+    type Item = Result<XmlEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.fill_pending() {
+            Ok(true) => self.pending.pop_front().map(Ok),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Read the XML declaration. Also returns the encoding, unless it is UTF-8.
+fn read_decl(ev: &BytesDecl) -> Result<(XmlEvent, Option<&'static Encoding>)> {
+    let version = String::from_utf8(ev.version()?.to_vec())?;
+    let (declared_encoding, encoding) = match ev.encoding() {
+        Some(res) => {
+            let label = res?;
+            let encoding = Encoding::for_label(&label).ok_or(Error::CannotDecode)?;
+            let declared = String::from_utf8(label.to_vec())?;
+            if encoding == UTF_8 {
+                (Some(declared), None)
+            } else {
+                (Some(declared), Some(encoding))
+            }
+        }
+        None => (None, None),
+    };
+    let standalone = match ev.standalone() {
+        Some(res) => {
+            let val = std::str::from_utf8(&res?)?.to_lowercase();
+            match val.as_str() {
+                "yes" => Some(true),
+                "no" => Some(false),
+                _ => {
+                    return Err(Error::MalformedXML(
+                        "Standalone Document Declaration has non boolean value".to_string(),
+                    ))
+                }
+            }
+        }
+        None => None,
+    };
+    let declaration = XmlEvent::Declaration {
+        version,
+        encoding: declared_encoding,
+        standalone,
+    };
+    Ok((declaration, encoding))
+}
+
+/// Maximal nesting of entity references within entity replacement text.
+const MAX_ENTITY_DEPTH: usize = 16;
+/// Maximal total length (in bytes) of the replacement text substituted for internal
+/// entity references in a document, including references nested in other entities.
+const MAX_ENTITY_EXPANSION_LENGTH: usize = 1 << 22;
+/// Maximal number of internal entity references substituted in a document.
+const MAX_ENTITY_SUBSTITUTIONS: usize = 1 << 16;
+
+/// Internal general entities declared in the document type declaration. An entity
+/// is expanded when it is first referenced and its replacement text is then reused.
+/// The amount of substituted text is limited for the whole document.
+#[derive(Debug, Default)]
+struct InternalEntities {
+    // Replacement text of declared entities, before expansion.
+    declared: HashMap<String, String>,
+    // Replacement text of entities which are already expanded.
+    expanded: HashMap<String, String>,
+    expanded_length: usize,
+    substitutions: usize,
+}
+
+impl InternalEntities {
+    /// Read the internal general entities declared in `doctype`.
+    fn declare(&mut self, doctype: &str) {
+        for entity in parse_doctype(doctype).entities {
+            if let (false, Some(value)) = (entity.is_parameter, entity.value) {
+                // If an entity is declared more than once, the first declaration is used.
+                self.declared.entry(entity.name).or_insert(value);
+            }
+        }
+    }
+
+    /// Expand the internal entities referenced in `text`, so that they can be resolved
+    /// by [`InternalEntities::resolve`]. `entities` take precedence over declared entities.
+    fn expand_references(&mut self, text: &[u8], entities: &HashMap<String, String>) -> Result<()> {
+        if self.declared.is_empty() {
             return Ok(());
         }
-        // Handle rest of the events
-        self.parse_content(xmlreader)
+        let text = std::str::from_utf8(text)?;
+        for name in entity_references(text) {
+            if !entities.contains_key(name) && self.declared.contains_key(name) {
+                self.substitute(name, entities, 0)?;
+            }
+        }
+        Ok(())
     }
 
-    fn parse_content<B: BufRead>(&mut self, mut reader: Reader<B>) -> Result<()> {
-        let mut buf = Vec::with_capacity(200); // reduce time increasing capacity at start.
+    /// Resolve `name` to the replacement text of a custom or an expanded internal entity.
+    fn resolve<'a>(&'a self, entities: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+        entities
+            .get(name)
+            .or_else(|| self.expanded.get(name))
+            .map(|value| value.as_str())
+    }
 
-        loop {
-            let ev = reader.read_event_into(&mut buf)?;
+    /// Expand the declared entity `name` (unless it is already expanded) and count
+    /// one substitution of its replacement text against the limits.
+    fn substitute(
+        &mut self,
+        name: &str,
+        entities: &HashMap<String, String>,
+        depth: usize,
+    ) -> Result<()> {
+        if !self.expanded.contains_key(name) {
+            if depth > MAX_ENTITY_DEPTH {
+                return Err(Error::EntityExpansionLimit);
+            }
+            let value = self.declared[name].clone();
+            let expanded = self.expand_value(&value, entities, depth)?;
+            self.expanded.insert(name.to_string(), expanded);
+        }
+        self.substitutions += 1;
+        self.expanded_length += self.expanded[name].len();
+        if self.substitutions > MAX_ENTITY_SUBSTITUTIONS
+            || self.expanded_length > MAX_ENTITY_EXPANSION_LENGTH
+        {
+            return Err(Error::EntityExpansionLimit);
+        }
+        Ok(())
+    }
 
-            if self.handle_event(ev)? {
-                return if self.element_stack.len() == 1 {
-                    // Should only have container remaining in element_stack
-                    Ok(())
-                } else {
-                    Err(Error::MalformedXML("Closing tag not found.".to_string()))
-                };
+    /// Replace entity and character references in the replacement text of an internal entity.
+    fn expand_value(
+        &mut self,
+        value: &str,
+        entities: &HashMap<String, String>,
+        depth: usize,
+    ) -> Result<String> {
+        let mut result = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find('&') {
+            result.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find(';')
+                .map(|end| start + end)
+                .ok_or_else(|| {
+                    Error::MalformedXML(format!("Unterminated entity in `{}`", value))
+                })?;
+            let name = &rest[start + 1..end];
+            if let Some(replacement) = entities.get(name) {
+                result.push_str(replacement);
+            } else if self.declared.contains_key(name) {
+                self.substitute(name, entities, depth + 1)?;
+                result.push_str(&self.expanded[name]);
+            } else {
+                // Character references and predefined entities.
+                result.push_str(&unescape(&rest[start..=end])?);
             }
+            rest = &rest[end + 1..];
         }
+        result.push_str(rest);
+        Ok(result)
+    }
+}
+
+/// Names of the entities referenced in `text` (`&name;`), without character references.
+fn entity_references(text: &str) -> impl Iterator<Item = &str> {
+    text.split('&').skip(1).filter_map(|part| {
+        let (name, _) = part.split_once(';')?;
+        (!name.starts_with('#')).then_some(name)
+    })
+}
+
+fn read_start(
+    ev: &BytesStart,
+    self_closing: bool,
+    opts: &ReadOptions,
+    internal_entities: &mut InternalEntities,
+) -> Result<XmlEvent> {
+    let name = String::from_utf8(ev.name().into_inner().to_vec())?;
+    let mut namespace_decls = HashMap::new();
+    let mut attributes = HashMap::new();
+    let mut attribute_order = Vec::new();
+    // Duplicates are checked below, so that they can be reported with a dedicated error.
+    for (count, attr) in ev.attributes().with_checks(false).enumerate() {
+        if let Some(limit) = opts.max_attributes_per_element {
+            if count >= limit {
+                return Err(Error::TooManyAttributes {
+                    element: name,
+                    limit,
+                });
+            }
+        }
+        let mut attr = attr?;
+        attr.value = Cow::Owned(normalize_space(&attr.value));
+        let key = String::from_utf8(attr.key.into_inner().to_vec())?;
+        internal_entities.expand_references(&attr.value, &opts.entities)?;
+        let value = attr
+            .unescape_value_with(|name| internal_entities.resolve(&opts.entities, name))?
+            .into_owned();
+        let previous = if key == "xmlns" {
+            namespace_decls.insert(String::new(), value)
+        } else if let Some(prefix) = key.strip_prefix("xmlns:") {
+            namespace_decls.insert(prefix.to_owned(), value)
+        } else {
+            let previous = attributes.insert(key.clone(), value);
+            if previous.is_none() {
+                attribute_order.push(key.clone());
+            }
+            previous
+        };
+        if previous.is_some() && opts.error_on_duplicate_attribute {
+            return Err(Error::DuplicateAttribute {
+                element: name,
+                attribute: key,
+            });
+        }
+    }
+    Ok(XmlEvent::ElementStart {
+        name,
+        attributes,
+        attribute_order,
+        namespace_decls,
+        self_closing,
+    })
+}
+
+// Sniff encoding and consume BOM. Returns the encoding to start decoding with
+// (`None` for UTF-8), and the encoding given by the BOM, if there is one.
+type SniffedEncoding = (Option<&'static Encoding>, Option<&'static Encoding>);
+fn sniff_encoding<R: Read>(decodereader: &mut DecodeReader<R>) -> Result<SniffedEncoding> {
+    let bytes = decodereader.fill_buf()?;
+    let encoding = match bytes {
+        [0x3c, 0x3f, ..] => (None, None), // UTF-8 '<?'
+        [0xfe, 0xff, ..] => {
+            // UTF-16 BE BOM
+            decodereader.consume(2);
+            (Some(UTF_16BE), Some(UTF_16BE))
+        }
+        [0xff, 0xfe, ..] => {
+            // UTF-16 LE BOM
+            decodereader.consume(2);
+            (Some(UTF_16LE), Some(UTF_16LE))
+        }
+        [0xef, 0xbb, 0xbf, ..] => {
+            // UTF-8 BOM
+            decodereader.consume(3);
+            (None, Some(UTF_8))
+        }
+        [0x00, 0x3c, 0x00, 0x3f, ..] => (Some(UTF_16BE), None),
+        [0x3c, 0x00, 0x3f, 0x00, ..] => (Some(UTF_16LE), None),
+        _ => (None, None), // Try decoding it with UTF-8
+    };
+    Ok(encoding)
+}
+
+/// A conservative estimate of the average size of an element in an XML document (typical
+/// documents have 50-100 bytes per element). It is better to underestimate the number of
+/// elements, since the storage still grows as needed, but an overestimate wastes memory.
+const BYTES_PER_ELEMENT: usize = 128;
+
+/// Number of events between two progress reports, see [`DocumentParser::parse_reader_with_progress`].
+const PROGRESS_INTERVAL: usize = 256;
+
+/// Builds a [`Document`] from [`XmlEvent`]s.
+pub(crate) struct DocumentParser<'a> {
+    doc: &'a mut Document,
+    read_opts: ReadOptions,
+    // Open elements, together with a flag indicating the element is self-closing.
+    element_stack: Vec<(Element, bool)>,
+    // Element and attribute names seen so far, so that repeated names share storage.
+    names: HashSet<Arc<str>>,
+}
+
+impl<'a> DocumentParser<'a> {
+    /// Parse a reader of unknown length. `input_len` (in bytes), if known,
+    /// is used to estimate the number of elements.
+    pub(crate) fn parse_reader<R: Read>(
+        reader: R,
+        input_len: Option<usize>,
+        opts: ReadOptions,
+    ) -> Result<Document> {
+        let mut doc = Document::new();
+        let events = XmlEvents::new(reader, opts.clone())?;
+        DocumentParser::parse_events(&mut doc, events, input_len, opts, |_| ())?;
+        Ok(doc)
+    }
+
+    /// Same as [`DocumentParser::parse_reader`], but calls `on_progress` with the position
+    /// of the parser (see [`XmlEvents::buffer_position`]) every [`PROGRESS_INTERVAL`] events
+    /// and at the end of the document.
+    pub(crate) fn parse_reader_with_progress<R: Read, F: FnMut(usize)>(
+        reader: R,
+        opts: ReadOptions,
+        on_progress: F,
+    ) -> Result<Document> {
+        let mut doc = Document::new();
+        let events = XmlEvents::new(reader, opts.clone())?;
+        DocumentParser::parse_events(&mut doc, events, None, opts, on_progress)?;
+        Ok(doc)
+    }
+
+    /// Parse a string which is already decoded, ignoring the declared encoding.
+    pub(crate) fn parse_str(str: &str, opts: ReadOptions) -> Result<Document> {
+        let mut doc = Document::new();
+        DocumentParser::parse_str_into(&mut doc, str, opts)?;
+        Ok(doc)
+    }
+
+    /// Same as [`DocumentParser::parse_str`], but parses into `doc`, which must be empty.
+    /// On error, `doc` contains the part of the document parsed so far.
+    pub(crate) fn parse_str_into(doc: &mut Document, str: &str, opts: ReadOptions) -> Result<()> {
+        let events = XmlEvents::new_utf8(str.as_bytes(), opts.clone())?;
+        DocumentParser::parse_events(doc, events, Some(str.len()), opts, |_| ())
+    }
+
+    fn parse_events<R: Read, F: FnMut(usize)>(
+        doc: &mut Document,
+        mut events: XmlEvents<R>,
+        input_len: Option<usize>,
+        opts: ReadOptions,
+        mut on_progress: F,
+    ) -> Result<()> {
+        let capacity = opts
+            .expected_elements
+            .or_else(|| input_len.map(|len| len / BYTES_PER_ELEMENT))
+            .unwrap_or(0);
+        doc.store.reserve(capacity);
+        doc.bom = events.bom;
+        // Set when the declaration event is read.
+        doc.declaration = false;
+        let element_stack = vec![(doc.container(), false)];
+        let mut parser = DocumentParser {
+            doc,
+            read_opts: opts,
+            element_stack,
+            names: HashSet::new(),
+        };
+        let mut reported = 0;
+        let mut count = 0;
+        while let Some(event) = events.next() {
+            parser.handle_event(event?)?;
+            count += 1;
+            if count % PROGRESS_INTERVAL == 0 && events.buffer_position() > reported {
+                reported = events.buffer_position();
+                on_progress(reported);
+            }
+        }
+        if events.buffer_position() > reported {
+            on_progress(events.buffer_position());
+        }
+        Ok(())
+    }
+
+    fn intern(&mut self, name: String) -> Arc<str> {
+        if let Some(name) = self.names.get(name.as_str()) {
+            return name.clone();
+        }
+        let name: Arc<str> = name.into();
+        self.names.insert(name.clone());
+        name
+    }
+
+    fn parent(&self) -> Result<Element> {
+        self.element_stack
+            .last()
+            .map(|(elem, _)| *elem)
+            .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))
+    }
+
+    fn push_node(&mut self, node: Node) -> Result<()> {
+        let parent = self.parent()?;
+        parent.push_child(self.doc, node)
+    }
+
+    fn handle_event(&mut self, event: XmlEvent) -> Result<()> {
+        match event {
+            XmlEvent::Declaration {
+                version,
+                encoding,
+                standalone,
+            } => {
+                self.doc.version = version;
+                self.doc.encoding = encoding;
+                self.doc.standalone = standalone;
+                self.doc.declaration = true;
+            }
+            XmlEvent::ElementStart {
+                name,
+                attributes,
+                attribute_order,
+                namespace_decls,
+                self_closing,
+            } => {
+                if self.read_opts.require_single_root
+                    && self.element_stack.len() == 1
+                    && self.doc.root_element().is_some()
+                {
+                    return Err(Error::MultipleRootElements);
+                }
+                let name = self.intern(name);
+                let attribute_order = attribute_order
+                    .into_iter()
+                    .map(|name| self.intern(name))
+                    .collect();
+                let elem = Element::with_data(
+                    self.doc,
+                    name,
+                    attributes,
+                    attribute_order,
+                    namespace_decls,
+                );
+                self.push_node(Node::Element(elem))?;
+                self.element_stack.push((elem, self_closing));
+            }
+            XmlEvent::ElementEnd { .. } => {
+                let (elem, self_closing) = self
+                    .element_stack
+                    .pop()
+                    .ok_or_else(|| Error::MalformedXML("Malformed Element Tree".to_string()))?;
+                // distinguish <tag></tag> and <tag />
+                if !self_closing && !elem.has_children(self.doc) {
+                    if self.read_opts.empty_text_node || self.read_opts.preserve_formatting {
+                        elem.set_force_expanded(self.doc, true);
+                    }
+                    if self.read_opts.empty_text_node {
+                        elem.push_child(self.doc, Node::Text(String::new()))?;
+                    }
+                }
+            }
+            XmlEvent::Text(text) => self.push_node(Node::Text(text))?,
+            XmlEvent::RawText(text) => self.push_node(Node::RawText(text))?,
+            XmlEvent::Comment(text) => self.push_node(Node::Comment(text))?,
+            XmlEvent::CData(text) => self.push_node(Node::CData(text))?,
+            XmlEvent::PI(text) => self.push_node(Node::PI(text))?,
+            XmlEvent::DocType(text) => self.push_node(Node::DocType(text))?,
+        }
+        Ok(())
     }
 }
 
@@ -509,3 +1108,50 @@ pub fn normalize_space(bytes: &[u8]) -> Vec<u8> {
     }
     normalized
 }
+
+/// Returns true if `name` matches the [`Name`](https://www.w3.org/TR/xml/#NT-Name) production
+/// of the XML specification, i.e. it can be used as an element or attribute name.
+///
+/// ```rust
+/// use biodivine_xml_doc::is_valid_xml_name;
+///
+/// assert!(is_valid_xml_name("ns:element-1"));
+/// assert!(is_valid_xml_name("_émoji"));
+/// assert!(!is_valid_xml_name(""));
+/// assert!(!is_valid_xml_name("1st"));
+/// assert!(!is_valid_xml_name("two words"));
+/// ```
+pub fn is_valid_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if is_name_start_char(c) => chars.all(is_name_char),
+        _ => false,
+    }
+}
+
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}'
+        | '\u{D8}'..='\u{F6}'
+        | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}'
+        | '\u{37F}'..='\u{1FFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}'
+        | '\u{2C00}'..='\u{2FEF}'
+        | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}'
+        | '\u{FDF0}'..='\u{FFFD}'
+        | '\u{10000}'..='\u{EFFFF}'
+    )
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c)
+        || matches!(c,
+            '-' | '.' | '0'..='9' | '\u{B7}'
+            | '\u{300}'..='\u{36F}'
+            | '\u{203F}'..='\u{2040}'
+        )
+}
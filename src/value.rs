@@ -0,0 +1,153 @@
+use crate::document::{Document, Node};
+use crate::element::{is_valid_qualified_name, Element};
+use crate::error::{Error, Result};
+use std::collections::BTreeMap;
+
+/// A simplified, data-oriented view of an XML document, see [`Document::to_value`].
+///
+/// Every element becomes either a [`XmlValue::Text`] (if it has no attributes and no child
+/// elements) or a [`XmlValue::Map`]. In a map, attributes (including namespace declarations)
+/// are stored under their full name prefixed with `@` (e.g. `@id`, `@xmlns:x`), child
+/// elements under their full name, and the text of an element with mixed content under
+/// `#text`. If an element has several child elements with the same name, they are stored
+/// as one [`XmlValue::List`], in document order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum XmlValue {
+    /// The text content of an element without attributes and child elements.
+    Text(String),
+    /// Values of child elements which have the same name.
+    List(Vec<XmlValue>),
+    /// Attributes, child elements and text of an element.
+    Map(BTreeMap<String, XmlValue>),
+}
+
+impl XmlValue {
+    /// Returns the text if the value is `Text`.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            XmlValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the values if the value is `List`.
+    pub fn as_list(&self) -> Option<&[XmlValue]> {
+        match self {
+            XmlValue::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns the entries if the value is `Map`.
+    pub fn as_map(&self) -> Option<&BTreeMap<String, XmlValue>> {
+        match self {
+            XmlValue::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Get the entry `key` if the value is `Map`. Same as `self.as_map()?.get(key)`.
+    pub fn get(&self, key: &str) -> Option<&XmlValue> {
+        self.as_map()?.get(key)
+    }
+}
+
+/// Convert the elements of `elements` into a map keyed by their full names.
+/// Repeated names are collected into a [`XmlValue::List`].
+pub(crate) fn elements_to_map(
+    doc: &Document,
+    elements: impl Iterator<Item = Element>,
+    map: &mut BTreeMap<String, XmlValue>,
+) {
+    for elem in elements {
+        let value = element_to_value(doc, elem);
+        let name = elem.full_name(doc);
+        match map.get_mut(name) {
+            Some(XmlValue::List(values)) => values.push(value),
+            Some(existing) => {
+                let first = std::mem::replace(existing, XmlValue::List(Vec::new()));
+                *existing = XmlValue::List(vec![first, value]);
+            }
+            None => {
+                map.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+/// Create elements named `name` from `value` and push them to `parent`.
+/// A [`XmlValue::List`] creates one element for every item.
+pub(crate) fn value_to_elements(
+    doc: &mut Document,
+    parent: Element,
+    name: &str,
+    value: &XmlValue,
+) -> Result<()> {
+    if let XmlValue::List(values) = value {
+        for value in values {
+            value_to_elements(doc, parent, name, value)?;
+        }
+        return Ok(());
+    }
+    if !is_valid_qualified_name(name) {
+        return Err(Error::InvalidName(name.to_string()));
+    }
+    let elem = Element::new(doc, name);
+    parent.push_child(doc, elem.as_node())?;
+    match value {
+        XmlValue::Text(text) => {
+            if !text.is_empty() {
+                elem.push_child(doc, Node::Text(text.clone()))?;
+            }
+        }
+        XmlValue::Map(map) => {
+            if let Some(XmlValue::Text(text)) = map.get("#text") {
+                elem.push_child(doc, Node::Text(text.clone()))?;
+            }
+            for (key, value) in map {
+                if let Some(attribute) = key.strip_prefix('@') {
+                    let Some(value) = value.as_text() else {
+                        continue;
+                    };
+                    if attribute == "xmlns" {
+                        elem.set_namespace_decl(doc, "", value);
+                    } else if let Some(prefix) = attribute.strip_prefix("xmlns:") {
+                        elem.set_namespace_decl(doc, prefix, value);
+                    } else {
+                        elem.set_attribute(doc, attribute, value)?;
+                    }
+                } else if key != "#text" {
+                    value_to_elements(doc, elem, key, value)?;
+                }
+            }
+        }
+        XmlValue::List(_) => unreachable!(),
+    }
+    Ok(())
+}
+
+fn element_to_value(doc: &Document, elem: Element) -> XmlValue {
+    let has_attributes = !elem.attributes(doc).is_empty() || !elem.namespace_decls(doc).is_empty();
+    if !has_attributes && elem.child_elements_iter(doc).next().is_none() {
+        return XmlValue::Text(elem.own_text(doc));
+    }
+    let mut map = BTreeMap::new();
+    for (name, value) in elem.attributes(doc) {
+        map.insert(format!("@{}", name), XmlValue::Text(value.clone()));
+    }
+    for (prefix, value) in elem.namespace_decls(doc) {
+        let name = if prefix.is_empty() {
+            "@xmlns".to_string()
+        } else {
+            format!("@xmlns:{}", prefix)
+        };
+        map.insert(name, XmlValue::Text(value.clone()));
+    }
+    let text = elem.own_text(doc);
+    let text = text.trim();
+    if !text.is_empty() {
+        map.insert("#text".to_string(), XmlValue::Text(text.to_string()));
+    }
+    elements_to_map(doc, elem.child_elements_iter(doc), &mut map);
+    XmlValue::Map(map)
+}
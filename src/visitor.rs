@@ -0,0 +1,61 @@
+use crate::document::Document;
+use crate::element::Element;
+
+/// Receives callbacks while walking a document tree using [`Document::walk`].
+///
+/// Nodes are visited in document order. Every element results in a call to
+/// [`Visitor::visit_element_start`], followed by the calls for its children
+/// and a final call to [`Visitor::visit_element_end`]. All methods do nothing by default,
+/// so you only need to implement the ones you are interested in.
+///
+/// # Examples
+///
+/// ```rust
+/// use biodivine_xml_doc::{Document, Element, Visitor};
+///
+/// /// Computes the maximal depth of the element tree.
+/// #[derive(Default)]
+/// struct DepthCounter {
+///     depth: usize,
+///     max_depth: usize,
+/// }
+///
+/// impl Visitor for DepthCounter {
+///     fn visit_element_start(&mut self, _elem: Element, _doc: &Document) {
+///         self.depth += 1;
+///         self.max_depth = self.max_depth.max(self.depth);
+///     }
+///     fn visit_element_end(&mut self, _elem: Element, _doc: &Document) {
+///         self.depth -= 1;
+///     }
+/// }
+///
+/// let doc = Document::parse_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+/// <root>
+///     <a><b/></a>
+///     <c/>
+/// </root>
+/// "#).unwrap();
+///
+/// let mut counter = DepthCounter::default();
+/// doc.walk(&mut counter);
+/// assert_eq!(counter.max_depth, 3);
+/// ```
+pub trait Visitor {
+    /// Called when entering an element, before any of its children are visited.
+    fn visit_element_start(&mut self, _elem: Element, _doc: &Document) {}
+    /// Called when leaving an element, after all of its children were visited.
+    fn visit_element_end(&mut self, _elem: Element, _doc: &Document) {}
+    /// Called for every [`crate::Node::Text`].
+    fn visit_text(&mut self, _text: &str) {}
+    /// Called for every [`crate::Node::Comment`].
+    fn visit_comment(&mut self, _comment: &str) {}
+    /// Called for every [`crate::Node::CData`].
+    fn visit_cdata(&mut self, _cdata: &str) {}
+    /// Called for every [`crate::Node::PI`].
+    fn visit_pi(&mut self, _pi: &str) {}
+    /// Called for every [`crate::Node::DocType`].
+    fn visit_doctype(&mut self, _doctype: &str) {}
+    /// Called for every [`crate::Node::RawText`]. The text is not unescaped.
+    fn visit_raw_text(&mut self, _text: &str) {}
+}
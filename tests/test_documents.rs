@@ -15,7 +15,7 @@ impl PartialEq<Self> for TStr {
     }
 }
 
-impl<'a> fmt::Debug for TStr {
+impl fmt::Debug for TStr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "\n{}\n", self.0.trim())
     }
@@ -27,7 +27,7 @@ fn to_yaml(doc: &Document) -> String {
     write_line("Root:", depth, &mut buf);
     depth += 1;
     let container = doc.container();
-    render_nodes(doc, container.children(&doc), depth, &mut buf);
+    render_nodes(doc, container.children(doc), depth, &mut buf);
     buf
 }
 
@@ -75,6 +75,14 @@ fn render_nodes(doc: &Document, nodes: &Vec<Node>, depth: usize, buf: &mut Strin
                 depth,
                 buf,
             ),
+            Node::RawText(text) => write_line(
+                &format!(
+                    "- RawText: \"{}\"",
+                    text.replace("\n", r"\n").replace("\r", r"\r")
+                ),
+                depth,
+                buf,
+            ),
         }
     }
 }
@@ -87,18 +95,18 @@ fn render_element(doc: &Document, elem: Element, mut depth: usize, buf: &mut Str
     write_line(&format!("name: {}", name), depth, buf);
 
     let attrs = elem.attributes(doc);
-    if attrs.len() > 0 {
+    if !attrs.is_empty() {
         write_line("attributes:", depth, buf);
         write_hashmap_alphabetical(attrs, depth, buf);
     }
 
     let namespaces = elem.namespace_decls(doc);
-    if namespaces.len() > 0 {
+    if !namespaces.is_empty() {
         write_line("namespaces:", depth, buf);
         write_hashmap_alphabetical(namespaces, depth, buf);
     }
     let children = elem.children(doc);
-    if children.len() > 0 {
+    if !children.is_empty() {
         write_line("children:", depth, buf);
         depth += 1;
         render_nodes(doc, children, depth, buf);
@@ -141,7 +149,7 @@ fn get_expected(file_name: &str) -> TStr {
 // read(write(doc)) should be doc.
 // just a basic test for writing.
 fn test_write(doc: &Document) -> TStr {
-    let expected = TStr(to_yaml(&doc));
+    let expected = TStr(to_yaml(doc));
     let written_xml = doc.write_str().unwrap();
     println!("{:?}", &written_xml);
     let new_doc = Document::from_str(&written_xml).unwrap();
@@ -174,11 +182,13 @@ where
     ];
 
     for k in opts.iter().multi_cartesian_product() {
-        let mut read_options = ReadOptions::default();
-        read_options.empty_text_node = *k[0];
-        read_options.trim_text = *k[1];
-        read_options.ignore_whitespace_only = *k[2];
-        read_options.require_decl = *k[3];
+        let read_options = ReadOptions {
+            empty_text_node: *k[0],
+            trim_text: *k[1],
+            ignore_whitespace_only: *k[2],
+            require_decl: *k[3],
+            ..ReadOptions::default()
+        };
         let expected_name: String = expected(&read_options).into();
         let expected = get_expected(&expected_name);
 
@@ -187,7 +197,7 @@ where
             Err(error) => {
                 println!("{:?}", error);
                 let debug_str = format!("{:?}", error);
-                let variant_name = debug_str.splitn(2, "(").next().unwrap();
+                let variant_name = debug_str.split('(').next().unwrap();
                 TStr(format!("error: {}", variant_name))
             }
         };
@@ -1,4 +1,4 @@
-use biodivine_xml_doc::{Document, Error, Node, ReadOptions};
+use biodivine_xml_doc::{parse_doctype, Document, Error, Node, ReadOptions, XmlEvent};
 
 #[test]
 fn test_normalize_attr() {
@@ -19,8 +19,10 @@ fn test_normalize_attr() {
 fn test_closing_tag_mismatch_err() {
     // no closing tag
     let xml = "<img>";
-    let mut opts = ReadOptions::default();
-    opts.require_decl = false;
+    let opts = ReadOptions {
+        require_decl: false,
+        ..ReadOptions::default()
+    };
     let doc = Document::parse_str_with_opts(xml, opts.clone());
     assert!(matches!(doc.unwrap_err(), Error::MalformedXML(_)));
 
@@ -42,8 +44,10 @@ fn test_unescape() {
     <!-- <&amp; cmt -->
     <!DOCTYPE &amp;>
     <?<&amp;?>"#;
-    let mut opts = ReadOptions::default();
-    opts.require_decl = false;
+    let opts = ReadOptions {
+        require_decl: false,
+        ..ReadOptions::default()
+    };
     let doc = Document::parse_str_with_opts(xml, opts).unwrap();
 
     let abc = doc.root_element().unwrap();
@@ -60,17 +64,561 @@ fn test_unescape() {
     if let Node::Comment(cmt) = comment {
         assert_eq!(cmt, " <&amp; cmt ");
     } else {
-        assert!(false);
+        panic!("Expected a comment node.");
     }
 
     let doctype = &doc.root_nodes()[3];
     if let Node::DocType(doc) = doctype {
         assert_eq!(doc, "&");
     } else {
-        assert!(false);
+        panic!("Expected a doctype node.");
     }
 
     let pi = &doc.root_nodes()[4];
     assert!(matches!(pi, Node::PI(_)));
     assert_eq!(pi.text_content(&doc), "<&amp;");
 }
+
+#[test]
+fn test_event_reader() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root xmlns:a="urn:a" a:x="1"><a:empty/>text<!--c--></root>"#;
+    let events: Vec<XmlEvent> = Document::event_reader(xml.as_bytes(), ReadOptions::default())
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(events.len(), 7);
+    assert_eq!(
+        events[0],
+        XmlEvent::Declaration {
+            version: "1.0".to_string(),
+            encoding: Some("UTF-8".to_string()),
+            standalone: None,
+        }
+    );
+    match &events[1] {
+        XmlEvent::ElementStart {
+            name,
+            attributes,
+            attribute_order,
+            namespace_decls,
+            self_closing,
+        } => {
+            assert_eq!(name, "root");
+            assert_eq!(attributes.get("a:x").unwrap(), "1");
+            assert_eq!(attribute_order, &vec!["a:x".to_string()]);
+            assert_eq!(namespace_decls.get("a").unwrap(), "urn:a");
+            assert!(!self_closing);
+        }
+        _ => panic!("Expected an element start event."),
+    }
+    assert!(matches!(
+        &events[2],
+        XmlEvent::ElementStart { name, self_closing: true, .. } if name == "a:empty"
+    ));
+    assert_eq!(
+        events[3],
+        XmlEvent::ElementEnd {
+            name: "a:empty".to_string()
+        }
+    );
+    assert_eq!(events[4], XmlEvent::Text("text".to_string()));
+    assert_eq!(events[5], XmlEvent::Comment("c".to_string()));
+    assert_eq!(
+        events[6],
+        XmlEvent::ElementEnd {
+            name: "root".to_string()
+        }
+    );
+
+    // Errors are reported lazily, after the events that precede them.
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?><root><a>"#;
+    let mut events = Document::event_reader(xml.as_bytes(), ReadOptions::default()).unwrap();
+    assert!(matches!(
+        events.next(),
+        Some(Ok(XmlEvent::Declaration { .. }))
+    ));
+    assert!(matches!(
+        events.next(),
+        Some(Ok(XmlEvent::ElementStart { .. }))
+    ));
+    assert!(matches!(
+        events.next(),
+        Some(Ok(XmlEvent::ElementStart { .. }))
+    ));
+    assert!(matches!(events.next(), Some(Err(Error::MalformedXML(_)))));
+    assert!(events.next().is_none());
+}
+
+#[test]
+fn test_parse_doctype() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE note SYSTEM "note.dtd" [
+    <!-- <!ENTITY commented "no"> -->
+    <!ELEMENT note (#PCDATA)>
+    <!ATTLIST note kind CDATA "[kind]">
+    <!ENTITY % param "p">
+    <!ENTITY single 'It is "quoted"'>
+]>
+<note/>"#;
+    let doc = Document::parse_str(xml).unwrap();
+    let info = parse_doctype(doc.doctype().unwrap());
+    assert_eq!(info.name, "note");
+    let entities: Vec<(&str, Option<&str>, bool)> = info
+        .entities
+        .iter()
+        .map(|e| (e.name.as_str(), e.value.as_deref(), e.is_parameter))
+        .collect();
+    assert_eq!(
+        entities,
+        vec![
+            ("param", Some("p"), true),
+            ("single", Some("It is \"quoted\""), false)
+        ]
+    );
+
+    assert_eq!(parse_doctype("html").name, "html");
+    assert!(parse_doctype("html").entities.is_empty());
+    assert_eq!(Document::new().doctype(), None);
+}
+
+#[test]
+fn test_custom_entities() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root name="&product; &amp; co">Buy &product;!</root>"#;
+    assert!(matches!(
+        Document::parse_str(xml),
+        Err(Error::UnknownEntity(name)) if name == "product"
+    ));
+
+    let mut opts = ReadOptions::default();
+    opts.entities
+        .insert("product".to_string(), "<Widget>".to_string());
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(root.attribute(&doc, "name"), Some("<Widget> & co"));
+    assert_eq!(root.text_content(&doc), "Buy <Widget>!");
+}
+
+#[test]
+fn test_internal_entities() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE root [
+    <!ENTITY name "Widget">
+    <!ENTITY full "&name; &amp; &#60;Co&#62;">
+    <!ENTITY name "Ignored">
+]>
+<root attr="&full;">&full;</root>"#;
+    // Not expanded by default.
+    assert!(matches!(
+        Document::parse_str(xml),
+        Err(Error::UnknownEntity(name)) if name == "full"
+    ));
+
+    let mut opts = ReadOptions {
+        expand_internal_entities: true,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts.clone()).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(root.text_content(&doc), "Widget & <Co>");
+    assert_eq!(root.attribute(&doc, "attr"), Some("Widget & <Co>"));
+
+    // User supplied entities take precedence.
+    opts.entities
+        .insert("name".to_string(), "Gadget".to_string());
+    let doc = Document::parse_str_with_opts(xml, opts.clone()).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(root.text_content(&doc), "Gadget & <Co>");
+
+    // Recursive entities are rejected.
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE root [
+    <!ENTITY a "&b;">
+    <!ENTITY b "&a;">
+]>
+<root>&a;</root>"#;
+    assert!(matches!(
+        Document::parse_str_with_opts(xml, opts.clone()),
+        Err(Error::EntityExpansionLimit)
+    ));
+
+    // So are entities which expand exponentially.
+    let mut doctype = String::from("<!ENTITY e0 \"laugh\">");
+    for i in 1..10 {
+        let refs = format!("&e{};", i - 1).repeat(10);
+        doctype.push_str(&format!("<!ENTITY e{} \"{}\">", i, refs));
+    }
+    let xml = format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE root [{}]><root>&e9;</root>",
+        doctype
+    );
+    assert!(matches!(
+        Document::parse_str_with_opts(&xml, opts),
+        Err(Error::EntityExpansionLimit)
+    ));
+}
+
+#[test]
+fn test_internal_entity_limits() {
+    let opts = ReadOptions {
+        expand_internal_entities: true,
+        ..ReadOptions::default()
+    };
+    let entities = |first: &str, count: usize| {
+        let mut doctype = format!("<!ENTITY e0 \"{}\">", first);
+        for i in 1..count {
+            let refs = format!("&e{};", i - 1).repeat(10);
+            doctype.push_str(&format!("<!ENTITY e{} \"{}\">", i, refs));
+        }
+        doctype
+    };
+
+    // Every entity is expanded only once, so a deep fan-out of empty entities is cheap.
+    let xml = format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE root [{}]><root a=\"&e16;\">{}</root>",
+        entities("", 17),
+        "&e16;".repeat(100)
+    );
+    let doc = Document::parse_str_with_opts(&xml, opts.clone()).unwrap();
+    assert_eq!(doc.root_element().unwrap().text_content(&doc), "");
+    // Entities which are never referenced are not expanded at all.
+    let xml = format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE root [{}]><root/>",
+        entities("laugh", 17)
+    );
+    assert!(Document::parse_str_with_opts(&xml, opts.clone()).is_ok());
+
+    // The total amount of substituted text is limited, not just the size of one entity.
+    let doctype = entities(&"x".repeat(50), 5);
+    let xml = format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE root [{}]><root>&e4;</root>",
+        doctype
+    );
+    let doc = Document::parse_str_with_opts(&xml, opts.clone()).unwrap();
+    assert_eq!(
+        doc.root_element().unwrap().text_content(&doc).len(),
+        500_000
+    );
+    let xml = format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE root [{}]><root>{}</root>",
+        doctype,
+        "&e4;".repeat(200)
+    );
+    assert!(matches!(
+        Document::parse_str_with_opts(&xml, opts.clone()),
+        Err(Error::EntityExpansionLimit)
+    ));
+
+    // Replacement text is not parsed as markup.
+    let xml = r#"<?xml version="1.0"?><!DOCTYPE root [<!ENTITY b "<b>bold</b>">]><root>&b;</root>"#;
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    let root = doc.root_element().unwrap();
+    assert!(root.child_elements(&doc).is_empty());
+    assert_eq!(root.text_content(&doc), "<b>bold</b>");
+}
+
+#[test]
+fn test_duplicate_attribute() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root><item a="1" b="2" a="3"/></root>"#;
+    // By default, the last value of the attribute is used.
+    let doc = Document::parse_str(xml).unwrap();
+    let item = doc.root_element().unwrap().child_elements(&doc)[0];
+    assert_eq!(item.attribute(&doc, "a"), Some("3"));
+
+    let opts = ReadOptions {
+        error_on_duplicate_attribute: true,
+        ..ReadOptions::default()
+    };
+    match Document::parse_str_with_opts(xml, opts.clone()) {
+        Err(Error::DuplicateAttribute { element, attribute }) => {
+            assert_eq!(element, "item");
+            assert_eq!(attribute, "a");
+        }
+        _ => panic!("Expected a duplicate attribute error."),
+    }
+    let xml_ns = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root xmlns:a="urn:a" xmlns:a="urn:b"/>"#;
+    assert!(matches!(
+        Document::parse_str_with_opts(xml_ns, opts),
+        Err(Error::DuplicateAttribute { .. })
+    ));
+}
+
+#[test]
+fn test_xml_space_preserve() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+    <a> trimmed </a>
+    <pre xml:space="preserve"> kept <b> also kept </b><c xml:space="default"> trimmed </c> </pre>
+    <d> trimmed </d>
+</root>"#;
+    let doc = Document::parse_str(xml).unwrap();
+    let root = doc.root_element().unwrap();
+    let text = |name: &str| root.find(&doc, name).unwrap().text_content(&doc);
+    assert_eq!(text("a"), "trimmed");
+    assert_eq!(text("d"), "trimmed");
+    let pre = root.find(&doc, "pre").unwrap();
+    assert_eq!(pre.children(&doc)[0].as_text(), Some(" kept "));
+    assert_eq!(
+        pre.find(&doc, "b").unwrap().text_content(&doc),
+        " also kept "
+    );
+    assert_eq!(pre.find(&doc, "c").unwrap().text_content(&doc), "trimmed");
+    assert_eq!(pre.children(&doc)[3].as_text(), Some(" "));
+
+    // Whitespace-only text is kept as well.
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?><r xml:space="preserve">   </r>"#;
+    let opts = ReadOptions {
+        trim_text: false,
+        ignore_whitespace_only: true,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    let root = doc.root_element().unwrap();
+    assert_eq!(root.children(&doc).len(), 1);
+    assert_eq!(root.children(&doc)[0].as_text(), Some("   "));
+}
+
+#[test]
+fn test_collapse_whitespace() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+    <p>  Some
+        text&#10;with   <b> bold </b>
+        words </p>
+    <c><![CDATA[  kept   as is  ]]></c>
+    <pre xml:space="preserve">  kept   as is  </pre>
+</root>"#;
+    for trim_text in [true, false] {
+        let opts = ReadOptions {
+            trim_text,
+            collapse_whitespace: true,
+            ..ReadOptions::default()
+        };
+        let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+        let root = doc.root_element().unwrap();
+        // Whitespace-only text between the elements is dropped.
+        assert_eq!(root.children(&doc).len(), 3);
+        let p = root.find(&doc, "p").unwrap();
+        let texts: Vec<&str> = p
+            .children(&doc)
+            .iter()
+            .filter_map(|n| n.as_text())
+            .collect();
+        assert_eq!(texts, vec!["Some text\nwith", "words"]);
+        assert_eq!(p.find(&doc, "b").unwrap().text_content(&doc), "bold");
+        let c = root.find(&doc, "c").unwrap();
+        assert_eq!(c.children(&doc)[0].as_cdata(), Some("  kept   as is  "));
+        let pre = root.find(&doc, "pre").unwrap();
+        assert_eq!(pre.text_content(&doc), "  kept   as is  ");
+    }
+}
+
+#[test]
+fn test_keep_raw_text() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+  <a>Tom &amp; Jerry &#x26; &gt;</a>
+</root>"#;
+    let opts = ReadOptions {
+        keep_raw_text: true,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts.clone()).unwrap();
+    let a = doc.find("a").unwrap();
+    assert!(matches!(
+        &a.children(&doc)[0],
+        Node::RawText(text) if text == "Tom &amp; Jerry &#x26; &gt;"
+    ));
+    assert_eq!(a.text_content(&doc), "Tom & Jerry & >");
+    // The escaping is written back verbatim.
+    assert_eq!(doc.write_str().unwrap(), xml);
+
+    // The raw text still has to be well-formed.
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?><root>&unknown;</root>"#;
+    let result = Document::parse_str_with_opts(xml, opts);
+    assert!(matches!(result, Err(Error::UnknownEntity(_))));
+}
+
+#[test]
+fn test_require_single_root() {
+    let opts = |require_single_root| ReadOptions {
+        require_single_root,
+        ..ReadOptions::default()
+    };
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?><!-- c --><a><b/></a><!-- c -->"#;
+    assert!(Document::parse_str_with_opts(xml, opts(true)).is_ok());
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?><a/><!-- c --><b/>"#;
+    let doc = Document::parse_str_with_opts(xml, opts(false)).unwrap();
+    assert_eq!(doc.root_elements().len(), 2);
+    let result = Document::parse_str_with_opts(xml, opts(true));
+    assert!(matches!(result, Err(Error::MultipleRootElements)));
+}
+
+#[test]
+fn test_max_attributes_per_element() {
+    let opts = |max_attributes_per_element| ReadOptions {
+        max_attributes_per_element,
+        ..ReadOptions::default()
+    };
+    let xml =
+        r#"<?xml version="1.0" encoding="UTF-8"?><a xmlns:x="x" x:b="1" c="2"><d e="3"/></a>"#;
+    assert!(Document::parse_str_with_opts(xml, opts(None)).is_ok());
+    assert!(Document::parse_str_with_opts(xml, opts(Some(3))).is_ok());
+    let result = Document::parse_str_with_opts(xml, opts(Some(2)));
+    assert!(matches!(
+        result,
+        Err(Error::TooManyAttributes { element, limit: 2 }) if element == "a"
+    ));
+}
+
+#[test]
+fn test_prolog_and_epilog() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Copyright (c) 2024 -->
+<!DOCTYPE root>
+<?xml-stylesheet href="style.css"?>
+<root><!-- inner --></root>
+<!-- generated -->
+<?done?>
+"#;
+    let doc = Document::parse_str(xml).unwrap();
+    let nodes = doc.root_nodes();
+    assert_eq!(nodes.len(), 6);
+    assert_eq!(nodes[0].as_comment(), Some(" Copyright (c) 2024 "));
+    assert!(matches!(&nodes[1], Node::DocType(text) if text == "root"));
+    assert_eq!(nodes[2].as_pi(), Some("xml-stylesheet href=\"style.css\""));
+    assert_eq!(nodes[3].as_element(), doc.root_element());
+    assert_eq!(nodes[4].as_comment(), Some(" generated "));
+    assert_eq!(nodes[5].as_pi(), Some("done"));
+
+    let prolog = doc.prolog_nodes();
+    assert_eq!(prolog.len(), 3);
+    assert_eq!(prolog[0].as_comment(), Some(" Copyright (c) 2024 "));
+    let epilog = doc.epilog_nodes();
+    assert_eq!(epilog.len(), 2);
+    assert_eq!(epilog[0].as_comment(), Some(" generated "));
+
+    // The order is kept when the document is written.
+    let doc = Document::parse_str(&doc.write_str().unwrap()).unwrap();
+    assert_eq!(doc.prolog_nodes().len(), 3);
+    assert_eq!(doc.epilog_nodes().len(), 2);
+
+    // Without a root element, all nodes are in the prolog.
+    let mut doc = Document::new();
+    doc.push_root_node(Node::Comment("c".to_string())).unwrap();
+    assert_eq!(doc.prolog_nodes().len(), 1);
+    assert!(doc.epilog_nodes().is_empty());
+}
+
+#[test]
+fn test_byte_order_mark() {
+    fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+        std::iter::once(0xfeff)
+            .chain(text.encode_utf16())
+            .flat_map(|unit| {
+                if little_endian {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                }
+            })
+            .collect()
+    }
+
+    let xml = "<?xml version=\"1.0\" encoding=\"UTF-16\"?>\n<root>Jürgen</root>";
+    let utf8 = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root>Jürgen</root>";
+    // The BOM wins over the declared encoding.
+    let mismatched = "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<root>Jürgen</root>";
+    let mut utf8_bom = vec![0xef, 0xbb, 0xbf];
+    utf8_bom.extend_from_slice(utf8.as_bytes());
+    let mut mismatched_bom = vec![0xef, 0xbb, 0xbf];
+    mismatched_bom.extend_from_slice(mismatched.as_bytes());
+    for (bytes, bom, roundtrip) in [
+        (utf16(xml, true), "UTF-16LE", true),
+        (utf16(xml, false), "UTF-16BE", true),
+        (utf8_bom, "UTF-8", true),
+        (mismatched_bom, "UTF-8", false),
+    ] {
+        let doc = Document::parse_bytes(&bytes).unwrap();
+        assert_eq!(doc.byte_order_mark(), Some(bom));
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.text_content(&doc), "Jürgen");
+        if roundtrip {
+            let mut written = Vec::new();
+            doc.write(&mut written).unwrap();
+            assert_eq!(written, bytes);
+        }
+    }
+
+    // The BOM is ignored if another encoding is enforced.
+    let opts = ReadOptions {
+        encoding: Some("UTF-8".to_string()),
+        enforce_encoding: true,
+        ..ReadOptions::default()
+    };
+    let result = Document::parse_bytes_with_opts(&utf16(xml, true), opts);
+    assert!(result.is_err());
+
+    let doc = Document::parse_bytes(utf8.as_bytes()).unwrap();
+    assert_eq!(doc.byte_order_mark(), None);
+}
+
+#[test]
+fn test_read_str() {
+    let decl = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+    let mut doc = Document::new();
+    doc.set_id_attributes(vec!["key".to_string()]);
+    doc.read_str(&format!(r#"{}<root><a key="x"/></root>"#, decl))
+        .unwrap();
+    assert!(doc.get_element_by_id("x").is_some());
+
+    // A failed read leaves the document empty, but keeps the id attributes.
+    assert!(doc.read_str(&format!("{}<root><a></root>", decl)).is_err());
+    assert!(doc.is_empty());
+    assert!(doc.root_nodes().is_empty());
+    assert_eq!(doc.id_attributes(), ["key"]);
+
+    doc.read_str(&format!(r#"{}<root key="y"/>"#, decl))
+        .unwrap();
+    assert_eq!(doc.get_element_by_id("y"), doc.root_element());
+}
+
+#[test]
+fn test_parse_progress() {
+    // Much larger than the read buffer of the parser.
+    let prefix = r#"<?xml version="1.0" encoding="UTF-8"?><root>"#;
+    let xml = format!("{}{}</root>", prefix, "<item/>".repeat(10_000));
+    let mut progress = Vec::new();
+    let doc = Document::parse_reader_with_progress(xml.as_bytes(), ReadOptions::default(), |p| {
+        progress.push(p)
+    })
+    .unwrap();
+    assert_eq!(doc.node_count(), 10_001);
+    assert!(progress.len() > 10);
+    assert!(progress.windows(2).all(|w| w[0] < w[1]));
+    // The reported position is always at the end of a parsed element,
+    // not ahead of the parser.
+    for position in &progress {
+        assert_eq!((position - prefix.len()) % "<item/>".len(), 0);
+    }
+    assert_eq!(progress.last(), Some(&xml.len()));
+
+    // The byte order mark is counted, too.
+    let mut bytes = vec![0xef, 0xbb, 0xbf];
+    bytes.extend_from_slice(xml.as_bytes());
+    let mut last = 0;
+    Document::parse_reader_with_progress(bytes.as_slice(), ReadOptions::default(), |p| last = p)
+        .unwrap();
+    assert_eq!(last, bytes.len());
+
+    // So is the declaration, if the parser switches to the declared encoding after it.
+    let latin1 = xml.replace("UTF-8", "ISO-8859-1");
+    Document::parse_reader_with_progress(latin1.as_bytes(), ReadOptions::default(), |p| last = p)
+        .unwrap();
+    assert_eq!(last, latin1.len());
+}
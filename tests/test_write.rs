@@ -1,4 +1,6 @@
-use biodivine_xml_doc::{Document, Element, Node};
+use biodivine_xml_doc::{
+    AttributeOrder, Document, Element, Node, ReadOptions, TextEscaping, WriteOptions,
+};
 
 #[test]
 fn test_escape() {
@@ -37,3 +39,507 @@ fn test_write() {
     assert_eq!(doc.write_str().unwrap(), doc2.write_str().unwrap());
     std::fs::remove_file("test_file.xml").unwrap();
 }
+
+#[test]
+fn test_write_element() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root xmlns="http://default" xmlns:a="http://a" xmlns:b="http://b">
+  <a:item b:attr="1">
+    <child />
+  </a:item>
+</root>"#;
+    let doc = Document::parse_str(xml).unwrap();
+    let root = doc.root_element().unwrap();
+    let item = root.child_elements(&doc)[0];
+
+    // Only the namespaces used by the sub-tree are declared,
+    // and the declaration options are ignored.
+    let opts = WriteOptions {
+        indent_char: b'\t',
+        indent_size: 1,
+        write_decl: Some(true),
+        ..WriteOptions::default()
+    };
+    let expected = "<a:item b:attr=\"1\" xmlns=\"http://default\" xmlns:a=\"http://a\">\n\t<child/>\n</a:item>";
+    assert_eq!(item.write_str(&doc, opts).unwrap(), expected);
+
+    // The written fragment can be parsed on its own.
+    let opts = ReadOptions {
+        require_decl: false,
+        ..ReadOptions::default()
+    };
+    let fragment = Document::parse_str_with_opts(&root.outer_xml(&doc).unwrap(), opts).unwrap();
+    let opts = WriteOptions {
+        write_decl: Some(true),
+        ..WriteOptions::default()
+    };
+    assert_eq!(
+        fragment.write_str_with_opts(opts).unwrap(),
+        doc.write_str().unwrap()
+    );
+}
+
+#[test]
+fn test_raw_text() {
+    let mut doc = Document::new();
+    let container = doc.container();
+    let root = Element::build("root").push_to(&mut doc, container);
+    root.push_child(&mut doc, Node::Text("<b>&amp;</b>".to_string()))
+        .unwrap();
+    root.push_child(&mut doc, Node::RawText("<b>&amp;</b>".to_string()))
+        .unwrap();
+
+    let opts = WriteOptions {
+        write_decl: Some(false),
+        ..WriteOptions::default()
+    };
+    let xml = doc.write_str_with_opts(opts).unwrap();
+    assert_eq!(xml, "<root>&lt;b&gt;&amp;amp;&lt;/b&gt;<b>&amp;</b></root>");
+    assert_eq!(root.text_content(&doc), "<b>&amp;</b><b>&</b>");
+}
+
+#[test]
+fn test_write_encoding() {
+    for (file, encoding) in [
+        ("tests/documents/encoding1.xml", "EUC-KR"),
+        ("tests/documents/encoding2.xml", "UTF-16"),
+    ] {
+        let doc = Document::parse_file(file).unwrap();
+        let xml = doc.write_str().unwrap();
+        let decl = format!("<?xml version=\"1.0\" encoding=\"{}\"?>", encoding);
+        assert!(xml.starts_with(&decl));
+
+        // The written bytes use the declared encoding and can be read back.
+        let mut bytes = Vec::new();
+        doc.write(&mut bytes).unwrap();
+        assert_ne!(bytes, xml.as_bytes());
+        let doc2 = Document::parse_reader(bytes.as_slice()).unwrap();
+        assert_eq!(doc2.write_str().unwrap(), xml);
+    }
+
+    // The declared encoding can be overridden.
+    let doc = Document::parse_file("tests/documents/encoding1.xml").unwrap();
+    let opts = WriteOptions {
+        encoding: Some("UTF-8".to_string()),
+        ..WriteOptions::default()
+    };
+    let mut bytes = Vec::new();
+    doc.write_with_opts(&mut bytes, opts).unwrap();
+    let xml = String::from_utf8(bytes).unwrap();
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+}
+
+#[test]
+fn test_write_streaming() {
+    /// Records the size of every write.
+    struct Recorder(Vec<u8>, Vec<usize>);
+
+    impl std::io::Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.extend_from_slice(buf);
+            self.1.push(buf.len());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut doc = Document::parse_file("tests/documents/encoding1.xml").unwrap();
+    let root = doc.root_element().unwrap();
+    for i in 0..1000 {
+        let item = Element::build("item")
+            .text_content(i.to_string())
+            .finish(&mut doc);
+        root.push_child(&mut doc, item.as_node()).unwrap();
+    }
+    let xml = doc.write_str().unwrap();
+
+    // Neither UTF-8 nor encoded output is written at once.
+    for encoding in ["UTF-8", "EUC-KR", "UTF-16"] {
+        let opts = || WriteOptions {
+            encoding: Some(encoding.to_string()),
+            ..WriteOptions::default()
+        };
+        let mut recorder = Recorder(Vec::new(), Vec::new());
+        doc.write_with_opts(&mut recorder, opts()).unwrap();
+        assert!(recorder.1.len() > 1000);
+        assert!(recorder.1.iter().all(|size| *size < recorder.0.len() / 100));
+
+        let doc2 = Document::parse_reader(recorder.0.as_slice()).unwrap();
+        let expected = xml.replacen("EUC-KR", encoding, 1);
+        assert_eq!(doc2.write_str().unwrap(), expected);
+
+        // Buffering does not change the output.
+        let mut bytes = Vec::new();
+        doc.write_buffered(&mut bytes, opts()).unwrap();
+        assert_eq!(bytes, recorder.0);
+    }
+}
+
+#[test]
+fn test_write_standalone() {
+    for decl in [
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#,
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+    ] {
+        let xml = format!("{}\n<root/>", decl);
+        let doc = Document::parse_str(&xml).unwrap();
+        assert_eq!(doc.write_str().unwrap(), xml);
+    }
+}
+
+#[test]
+fn test_cdata_terminator() {
+    let content = "if (a[b[0]]> c) { x = \"<&>\"; } ]]>";
+    let mut doc = Document::new();
+    let container = doc.container();
+    let root = Element::build("root").push_to(&mut doc, container);
+    root.push_child(&mut doc, Node::CData(content.to_string()))
+        .unwrap();
+
+    let xml = doc.write_str().unwrap();
+    let doc = Document::parse_str(&xml).unwrap();
+    let root = doc.root_element().unwrap();
+    let parsed: String = root
+        .children(&doc)
+        .iter()
+        .map(|node| node.as_cdata().unwrap())
+        .collect();
+    assert_eq!(parsed, content);
+}
+
+#[test]
+fn test_text_escaping() {
+    let mut doc = Document::new();
+    let container = doc.container();
+    Element::build("root")
+        .attribute("attr", "'a' \"ü\" <&>")
+        .text_content("'a' \"ü\" <&> ]]>")
+        .push_to(&mut doc, container);
+
+    let write = |text_escaping| {
+        let opts = WriteOptions {
+            write_decl: Some(false),
+            text_escaping,
+            ..WriteOptions::default()
+        };
+        doc.write_str_with_opts(opts).unwrap()
+    };
+    assert_eq!(
+        write(TextEscaping::Minimal),
+        "<root attr=\"'a' &quot;ü&quot; &lt;&amp;>\">'a' \"ü\" &lt;&amp;> ]]&gt;</root>"
+    );
+    assert_eq!(
+        write(TextEscaping::Standard),
+        "<root attr=\"&apos;a&apos; &quot;ü&quot; &lt;&amp;&gt;\">&apos;a&apos; &quot;ü&quot; &lt;&amp;&gt; ]]&gt;</root>"
+    );
+    assert_eq!(
+        write(TextEscaping::AsciiOnly),
+        "<root attr=\"&apos;a&apos; &quot;&#xFC;&quot; &lt;&amp;&gt;\">&apos;a&apos; &quot;&#xFC;&quot; &lt;&amp;&gt; ]]&gt;</root>"
+    );
+
+    // All variants are read back the same.
+    let opts = ReadOptions {
+        require_decl: false,
+        ..ReadOptions::default()
+    };
+    for escaping in [
+        TextEscaping::Minimal,
+        TextEscaping::Standard,
+        TextEscaping::AsciiOnly,
+    ] {
+        let doc2 = Document::parse_str_with_opts(&write(escaping), opts.clone()).unwrap();
+        let root = doc2.root_element().unwrap();
+        assert_eq!(root.attribute(&doc2, "attr"), Some("'a' \"ü\" <&>"));
+        assert_eq!(root.text_content(&doc2), "'a' \"ü\" <&> ]]>");
+    }
+}
+
+#[test]
+fn test_expanded_empty_element() {
+    let xml = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<root>\n  <a/>\n  <b></b>\n  <c>text</c>\n</root>";
+    let doc = Document::parse_str(xml).unwrap();
+    assert_eq!(doc.write_str().unwrap(), xml);
+    // Only elements without content are marked.
+    let expanded: Vec<bool> = doc
+        .root_element()
+        .unwrap()
+        .child_elements(&doc)
+        .iter()
+        .map(|e| e.is_force_expanded(&doc))
+        .collect();
+    assert_eq!(expanded, vec![false, true, false]);
+
+    // Without empty text nodes, the output does not change: `<b></b>` is written as `<b/>`.
+    let opts = ReadOptions {
+        empty_text_node: false,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    assert_eq!(doc.write_str().unwrap(), xml.replace("<b></b>", "<b/>"));
+}
+
+#[test]
+fn test_xml_space_preserve() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root>
+  <a>
+    <b/>
+  </a>
+  <pre xml:space="preserve">
+    line 1
+  <b>  bold </b><c/>
+    line 2
+</pre>
+  <d/>
+</root>"#;
+    let doc = Document::parse_str(xml).unwrap();
+    assert_eq!(doc.write_str().unwrap(), xml);
+
+    // Indentation inside the element is not changed either.
+    let opts = WriteOptions {
+        indent_size: 4,
+        ..WriteOptions::default()
+    };
+    let pre = doc.find("pre").unwrap();
+    let expected = r#"<pre xml:space="preserve">
+    line 1
+  <b>  bold </b><c/>
+    line 2
+</pre>"#;
+    assert_eq!(pre.write_str(&doc, opts).unwrap(), expected);
+}
+
+#[test]
+fn test_void_elements() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<p>a<br></br>b<img src="x.png" /><span></span></p>"#;
+    let doc = Document::parse_str(xml).unwrap();
+    let opts = WriteOptions {
+        write_decl: Some(false),
+        preserve_formatting: true,
+        void_elements: ["br", "img"].iter().map(|name| name.to_string()).collect(),
+        ..WriteOptions::default()
+    };
+    assert_eq!(
+        doc.write_str_with_opts(opts).unwrap(),
+        r#"<p>a<br/>b<img src="x.png"/><span></span></p>"#
+    );
+}
+
+#[test]
+fn test_attribute_order() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<root z="1" a="2" b:m="3" xmlns:b="http://b"/>"#;
+    let mut doc = Document::parse_str(xml).unwrap();
+    let opts = || WriteOptions {
+        attribute_order: AttributeOrder::Source,
+        ..WriteOptions::default()
+    };
+    assert_eq!(doc.write_str_with_opts(opts()).unwrap(), xml);
+
+    // New attributes are appended, updated attributes keep their position.
+    let root = doc.root_element().unwrap();
+    root.set_attribute(&mut doc, "c", "4").unwrap();
+    root.set_attribute(&mut doc, "z", "5").unwrap();
+    root.mut_attributes(&mut doc).remove("a");
+    root.mut_attributes(&mut doc)
+        .insert("y".to_string(), "6".to_string());
+    root.set_attribute(&mut doc, "a", "7").unwrap();
+    let expected = r#"<root z="5" b:m="3" c="4" a="7" y="6" xmlns:b="http://b"/>"#;
+    let written = doc.write_str_with_opts(opts()).unwrap();
+    assert!(written.ends_with(expected));
+
+    let built = Element::build("item")
+        .attribute("second", "1")
+        .attribute("first", "2")
+        .finish(&mut doc);
+    let written = built.write_str(&doc, opts()).unwrap();
+    assert_eq!(written, r#"<item second="1" first="2"/>"#);
+    let written = built.write_str(&doc, WriteOptions::default()).unwrap();
+    assert_eq!(written, r#"<item first="2" second="1"/>"#);
+}
+
+#[test]
+fn test_display_errors() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut doc = Document::new();
+    let container = doc.container();
+    let root = Element::build("p:root").push_to(&mut doc, container);
+    assert!(matches!(
+        root.outer_xml(&doc),
+        Err(biodivine_xml_doc::Error::UndeclaredPrefix(prefix)) if prefix == "p"
+    ));
+    let result = catch_unwind(AssertUnwindSafe(|| format!("{}", root.display(&doc))));
+    assert!(result.is_err());
+
+    let mut doc = Document::new();
+    doc.push_root_node(Node::Comment("a--b".to_string()))
+        .unwrap();
+    assert!(matches!(
+        doc.write_str(),
+        Err(biodivine_xml_doc::Error::InvalidComment(_))
+    ));
+    let result = catch_unwind(AssertUnwindSafe(|| doc.to_string()));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_write_unusual_text() {
+    // A `str` cannot contain lone surrogates, so use the characters around the surrogate
+    // range, the replacement character, and characters which need surrogate pairs in UTF-16.
+    let text = "\u{D7FF}\u{E000}\u{FFFD}\u{10000}\u{10FFFF}";
+    let mut doc = Document::new();
+    let container = doc.container();
+    Element::build("root")
+        .attribute("attr", text)
+        .text_content(text)
+        .push_to(&mut doc, container);
+
+    // EUC-KR cannot encode these characters, so they are written as character references.
+    for encoding in ["UTF-8", "UTF-16LE", "UTF-16BE", "EUC-KR"] {
+        let opts = WriteOptions {
+            encoding: Some(encoding.to_string()),
+            ..WriteOptions::default()
+        };
+        let mut bytes = Vec::new();
+        doc.write_with_opts(&mut bytes, opts).unwrap();
+        let doc2 = Document::parse_reader(bytes.as_slice()).unwrap();
+        let root = doc2.root_element().unwrap();
+        assert_eq!(root.attribute(&doc2, "attr"), Some(text));
+        assert_eq!(root.text_content(&doc2), text);
+    }
+
+    // Unknown encodings are reported instead of panicking.
+    let opts = WriteOptions {
+        encoding: Some("unknown".to_string()),
+        ..WriteOptions::default()
+    };
+    let mut bytes = Vec::new();
+    let result = doc.write_with_opts(&mut bytes, opts);
+    assert!(matches!(
+        result,
+        Err(biodivine_xml_doc::Error::CannotDecode)
+    ));
+}
+
+#[test]
+fn test_preserve_formatting() {
+    let xml = std::fs::read_to_string("tests/documents/config.xml").unwrap();
+    let read_opts = ReadOptions {
+        preserve_formatting: true,
+        ..ReadOptions::default()
+    };
+    let write_opts = || WriteOptions {
+        preserve_formatting: true,
+        ..WriteOptions::default()
+    };
+    let mut doc = Document::parse_str_with_opts(&xml, read_opts).unwrap();
+    assert_eq!(doc.write_str_with_opts(write_opts()).unwrap(), xml);
+
+    // Modifying one element does not change the layout of the rest of the document.
+    let server = doc.root_element().unwrap().find(&doc, "server").unwrap();
+    let tls = server.find(&doc, "tls").unwrap();
+    tls.set_attribute(&mut doc, "enabled", "true").unwrap();
+    let expected = xml.replace(r#"<tls enabled="false"/>"#, r#"<tls enabled="true"/>"#);
+    assert_eq!(doc.write_str_with_opts(write_opts()).unwrap(), expected);
+
+    // Without the option, the attributes are sorted.
+    let written = doc.write_str().unwrap();
+    assert!(written.contains(r#"<config name="main" version="2">"#));
+
+    // `<tag></tag>` is kept even without empty text nodes.
+    let read_opts = ReadOptions {
+        preserve_formatting: true,
+        empty_text_node: false,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(&xml, read_opts).unwrap();
+    assert_eq!(doc.write_str_with_opts(write_opts()).unwrap(), xml);
+}
+
+#[test]
+fn test_invalid_comment_and_pi() {
+    let write = |node: Node| {
+        let mut doc = Document::new();
+        let container = doc.container();
+        let root = Element::build("root").push_to(&mut doc, container);
+        root.push_child(&mut doc, node).unwrap();
+        doc.write_str()
+    };
+    for comment in ["a -- b", "-->", "ends with -"] {
+        let result = write(Node::Comment(comment.to_string()));
+        assert!(matches!(
+            result,
+            Err(biodivine_xml_doc::Error::InvalidComment(_))
+        ));
+    }
+    for pi in ["target ?> text", "target?>"] {
+        let result = write(Node::PI(pi.to_string()));
+        assert!(matches!(
+            result,
+            Err(biodivine_xml_doc::Error::InvalidProcessingInstruction(_))
+        ));
+    }
+    assert!(write(Node::Comment(" - a - ".to_string())).is_ok());
+    assert!(write(Node::PI("target a > b ?".to_string())).is_ok());
+}
+
+#[test]
+fn test_c14n() {
+    // Example 3.3 of the Canonical XML 1.0 specification, without the DTD.
+    let xml = r#"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+    let expected = r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+    let opts = ReadOptions {
+        require_decl: false,
+        preserve_formatting: true,
+        ..ReadOptions::default()
+    };
+    let doc = Document::parse_str_with_opts(xml, opts).unwrap();
+    assert_eq!(doc.write_c14n().unwrap(), expected);
+
+    // Processing instructions outside the root element are kept, and special
+    // characters are escaped as character references.
+    let mut doc =
+        Document::parse_str("<?xml version=\"1.0\"?><?pi a?><r/><!--c--><?pi b?>").unwrap();
+    let root = doc.root_element().unwrap();
+    root.set_attribute(&mut doc, "attr", "\"\t\n\r<&>").unwrap();
+    root.push_child(&mut doc, Node::Text("\"\t\n\r<&>".to_string()))
+        .unwrap();
+    assert_eq!(
+        doc.write_c14n().unwrap(),
+        "<?pi a?>\n<r attr=\"&quot;&#x9;&#xA;&#xD;&lt;&amp;>\">\"\t\n&#xD;&lt;&amp;&gt;</r>\n<?pi b?>"
+    );
+}